@@ -4,7 +4,9 @@ use bevy_armory::prelude::*;
 fn main() {
     let mut app = App::new();
     app.add_plugins((DefaultPlugins, ItemPlugin));
+    app.init_resource::<InventoryHistory>();
     app.add_systems(Startup, setup);
+    app.add_systems(Update, (undo_redo, rotate_carried));
     app.run();
 }
 
@@ -18,25 +20,33 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
     let pickaxe = commands
         .spawn((
             Item,
+            ItemId("item::pickaxe".into()),
             Name::new("item::pickaxe"),
             Icon(asset_server.load_with_settings("pickaxe.png", nearest_sampler)),
             CanMineBlocks,
+            Shape::Rect {
+                width: 1,
+                height: 2,
+            },
         ))
         .id();
 
     let stone = commands
         .spawn((
             Item,
+            ItemId("item::stone".into()),
             Name::new("item::stone"),
             Icon(asset_server.load_with_settings("stone.png", nearest_sampler)),
+            MaxStack(8),
         ))
         .id();
+    let stored_pickaxe = commands.spawn((Item, Template(pickaxe))).id();
     let stored_stone1 = commands.spawn((Item, Template(stone), Count(2))).id();
     let stored_stone2 = commands.spawn((Item, Template(stone), Count(5))).id();
 
     // Spawn inventory
     let mut inv = Inventory::new::<4>();
-    inv.0[0] = Some(pickaxe);
+    inv.0[0] = Some(stored_pickaxe);
     inv.0[1] = Some(stored_stone1);
     let inv1 = commands.spawn((inv, Name::new("Inventory 1"))).id();
 
@@ -45,7 +55,33 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
     let inv2 = commands.spawn((inv, Name::new("Inventory 2"))).id();
 
     let inv = Inventory::new::<3>();
-    let inv3 = commands.spawn((inv, Name::new("Inventory 3"))).id();
+    let inv3 = commands
+        .spawn((
+            inv,
+            Name::new("Inventory 3"),
+            InventoryFilter(vec![ItemFilter::with::<CanMineBlocks>()]),
+        ))
+        .id();
+
+    let equipment = Equipment::default();
+    let filters =
+        equipment.filters([(EquipmentSlot::MainHand, ItemFilter::with::<CanMineBlocks>())]);
+    let inv4 = commands
+        .spawn((
+            equipment.inventory(),
+            equipment,
+            filters,
+            Name::new("Equipment"),
+        ))
+        .observe(|trigger: Trigger<ItemEquipped>| {
+            info!("Equipped {} in {:?}", trigger.item, trigger.slot);
+        })
+        .id();
+
+    let grid = GridInventory::new(4, 3);
+    let inv5 = commands
+        .spawn((grid.inventory(), grid, Name::new("Backpack")))
+        .id();
 
     // Spawn UI
     let ui_root = commands.spawn(Node::DEFAULT).id();
@@ -65,4 +101,39 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
         offset: SLOT_SIZE * Vec2::new(8.0, 3.0),
         inventory: InventoryUi { data: inv3 },
     });
+    commands.queue(SpawnInventoryUi {
+        parent: ui_root,
+        offset: SLOT_SIZE * Vec2::new(12.0, 0.0),
+        inventory: InventoryUi { data: inv4 },
+    });
+    commands.queue(SpawnGridInventoryUi {
+        parent: ui_root,
+        offset: SLOT_SIZE * Vec2::new(0.0, 7.0),
+        inventory: InventoryUi { data: inv5 },
+    });
+}
+
+fn undo_redo(mut commands: Commands, keys: Res<ButtonInput<KeyCode>>) {
+    if keys.just_pressed(KeyCode::KeyZ) {
+        commands.queue(UndoInventory);
+    }
+    if keys.just_pressed(KeyCode::KeyY) {
+        commands.queue(RedoInventory);
+    }
+}
+
+fn rotate_carried(
+    keys: Res<ButtonInput<KeyCode>>,
+    carriers: Query<Entity, With<Carry>>,
+    mut transactions: ResMut<InventoryTransactions>,
+) {
+    if !keys.just_pressed(KeyCode::KeyR) {
+        return;
+    }
+    for inventory in &carriers {
+        transactions.push(InventoryOperation::Rotate {
+            inventory,
+            index: 0,
+        });
+    }
 }
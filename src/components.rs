@@ -1,30 +1,343 @@
 //! Core components for items.
 
-use bevy::prelude::*;
+use bevy::{
+    ecs::{
+        component::ComponentId,
+        entity::{EntityMapper, MapEntities},
+        reflect::ReflectMapEntities,
+        world::DeferredWorld,
+    },
+    prelude::*,
+    utils::HashSet,
+};
+
+use crate::{
+    events::{InvalidPrototype, InvalidTemplate},
+    lifecycle::{clear_slots, inventory_removed, item_removed},
+    ownership::{inventory_inserted, inventory_replaced, ContainedIn, SharedItems},
+    params::validate_templates,
+};
 
 /// Marker component for items.
-#[derive(Component)]
+#[derive(Component, Reflect, Default)]
+#[component(on_remove = item_removed)]
+#[reflect(Component, Default)]
 pub struct Item;
 
+/// Marker for template items, which other items are based on and which aren't items on their own.
+/// Prototypes can't be put into an [`Inventory`] and never carry a [`Count`].
+#[derive(Component, Reflect, Default)]
+#[component(on_insert = prototype_inserted)]
+#[reflect(Component, Default)]
+pub struct Prototype;
+
 /// Image handle for this item.
-#[derive(Component)]
+#[derive(Component, Reflect, Clone)]
+#[reflect(Component)]
 pub struct Icon(pub Handle<Image>);
 
 /// Base this item on another item.
-#[derive(Component)]
+/// Replace it by inserting a new one, so [`DerivedItems`] of the templates stay in sync.
+#[derive(Component, Reflect)]
+#[component(on_insert = links_inserted::<Template>, on_replace = links_replaced::<Template>)]
+#[reflect(Component, MapEntities)]
 pub struct Template(pub Entity);
 
+impl MapEntities for Template {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.0 = entity_mapper.map_entity(self.0);
+    }
+}
+
+/// Base this item on multiple items, e.g. "iron" + "sword" + "legendary".
+/// Data is resolved in C3 linearization order of all templates, starting with [`Template`].
+/// Replace it by inserting a new one, so [`DerivedItems`] of the templates stay in sync.
+#[derive(Component, Reflect, Clone, Default)]
+#[component(on_insert = links_inserted::<Templates>, on_replace = links_replaced::<Templates>)]
+#[reflect(Component, Default, MapEntities)]
+pub struct Templates(pub Vec<Entity>);
+
+impl MapEntities for Templates {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        for template in &mut self.0 {
+            *template = entity_mapper.map_entity(*template);
+        }
+    }
+}
+
+/// Components linking an item to its templates.
+trait TemplateLinks: Component {
+    /// The other link component of the item.
+    type Other: TemplateLinks;
+
+    fn links(&self) -> Vec<Entity>;
+}
+
+impl TemplateLinks for Template {
+    type Other = Templates;
+
+    fn links(&self) -> Vec<Entity> {
+        vec![self.0]
+    }
+}
+
+impl TemplateLinks for Templates {
+    type Other = Template;
+
+    fn links(&self) -> Vec<Entity> {
+        self.0.clone()
+    }
+}
+
+/// Items directly based on this item, maintained from their [`Template`] and [`Templates`].
+/// It isn't reflected, since it's rebuilt whenever templates are inserted.
+#[derive(Component, Default)]
+pub struct DerivedItems(pub(crate) Vec<Entity>);
+
+impl DerivedItems {
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.0.iter().copied()
+    }
+}
+
+/// Rejects templates forming cycles or too deep chains, see [`validate_templates`].
+fn links_inserted<L: TemplateLinks>(mut world: DeferredWorld, entity: Entity, _: ComponentId) {
+    if let Err(error) = validate_templates(&world, entity) {
+        world.commands().entity(entity).remove::<L>();
+        world.commands().trigger(InvalidTemplate(error));
+        return;
+    }
+    let templates = world.get::<L>(entity).unwrap().links();
+    world.commands().queue(move |world: &mut World| {
+        for template in templates {
+            if world
+                .get::<L>(entity)
+                .is_none_or(|l| !l.links().contains(&template))
+            {
+                continue;
+            }
+            let Ok(mut template) = world.get_entity_mut(template) else {
+                continue;
+            };
+            let mut derived = template.entry::<DerivedItems>().or_default();
+            if !derived.0.contains(&entity) {
+                derived.0.push(entity);
+            }
+        }
+    });
+}
+
+fn links_replaced<L: TemplateLinks>(mut world: DeferredWorld, entity: Entity, _: ComponentId) {
+    // Item may still be derived through the other link component.
+    let kept = world
+        .get::<L::Other>(entity)
+        .map(TemplateLinks::links)
+        .unwrap_or_default();
+    for template in world.get::<L>(entity).unwrap().links() {
+        if kept.contains(&template) {
+            continue;
+        }
+        if let Some(mut derived) = world.get_mut::<DerivedItems>(template) {
+            derived.0.retain(|&e| e != entity);
+        }
+    }
+}
+
+/// Transitive traversal of [`DerivedItems`].
+pub trait DescendantsExt {
+    /// Items based on the template directly or through other items, each visited once.
+    fn iter_descendants(&self, template: Entity) -> impl Iterator<Item = Entity> + '_;
+}
+
+impl DescendantsExt for World {
+    fn iter_descendants(&self, template: Entity) -> impl Iterator<Item = Entity> + '_ {
+        descendants(template, |e| self.get::<DerivedItems>(e))
+    }
+}
+
+impl DescendantsExt for Query<'_, '_, &DerivedItems> {
+    fn iter_descendants(&self, template: Entity) -> impl Iterator<Item = Entity> + '_ {
+        descendants(template, |e| self.get(e).ok())
+    }
+}
+
+fn descendants<'a>(
+    template: Entity,
+    derived: impl Fn(Entity) -> Option<&'a DerivedItems> + 'a,
+) -> impl Iterator<Item = Entity> + 'a {
+    let mut visited = HashSet::from([template]);
+    let mut pending = vec![template];
+    let mut current = Vec::new().into_iter();
+    std::iter::from_fn(move || loop {
+        if let Some(item) = current.next() {
+            if visited.insert(item) {
+                pending.push(item);
+                return Some(item);
+            }
+            continue;
+        }
+        let next = pending.pop()?;
+        current = derived(next)
+            .map(|d| d.0.clone())
+            .unwrap_or_default()
+            .into_iter();
+    })
+}
+
 /// Stores data about amount of an item.
-/// This component shouldn't be used in template items.
-#[derive(Component)]
+/// It's removed from [`Prototype`]s, since they aren't stacks of items.
+#[derive(Component, Reflect)]
+#[component(on_insert = count_inserted)]
+#[reflect(Component)]
 pub struct Count(pub u32);
 
+/// Takes the prototype out of its slot and removes its [`Count`].
+fn prototype_inserted(mut world: DeferredWorld, entity: Entity, _: ComponentId) {
+    let contained = world.get::<ContainedIn>(entity).copied();
+    if contained.is_some() || world.contains_resource::<SharedItems>() {
+        world
+            .commands()
+            .queue(move |world: &mut World| clear_slots(world, entity, contained));
+    }
+    if world.get::<Count>(entity).is_some() {
+        remove_count(world, entity);
+    }
+}
+
+fn count_inserted(world: DeferredWorld, entity: Entity, _: ComponentId) {
+    if world.get::<Prototype>(entity).is_some() {
+        remove_count(world, entity);
+    }
+}
+
+/// Removed by a command, since both hooks run when a prototype is spawned with a count.
+fn remove_count(mut world: DeferredWorld, prototype: Entity) {
+    world.commands().queue(move |world: &mut World| {
+        let Ok(mut entity) = world.get_entity_mut(prototype) else {
+            return;
+        };
+        if entity.take::<Count>().is_some() {
+            world.trigger(InvalidPrototype(prototype));
+        }
+    });
+}
+
+/// Maximum amount of an item a single stack can hold.
+/// Items without this component can be stacked indefinitely.
+#[derive(Component, Reflect, Clone)]
+#[reflect(Component)]
+pub struct MaxStack(pub u32);
+
 /// Constant size container for items.
-#[derive(Component)]
-pub struct Inventory(pub Box<[Option<Entity>]>);
+/// Slots shouldn't be added or removed once the inventory is spawned.
+/// Each item can be in a single slot, see [`ContainedIn`](crate::ownership::ContainedIn).
+#[derive(Component, Reflect)]
+#[component(
+    on_insert = inventory_inserted,
+    on_replace = inventory_replaced,
+    on_remove = inventory_removed
+)]
+#[reflect(Component, MapEntities)]
+pub struct Inventory(pub Vec<Option<Entity>>);
 
 impl Inventory {
     pub fn new<const N: usize>() -> Self {
-        Self(Box::new(core::array::from_fn::<_, N, _>(|_| None)))
+        Self::with_size(N)
+    }
+
+    pub fn with_size(size: usize) -> Self {
+        Self(vec![None; size])
+    }
+}
+
+impl MapEntities for Inventory {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        for item in self.0.iter_mut().flatten() {
+            *item = entity_mapper.map_entity(*item);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn derived(world: &World, template: Entity) -> Vec<Entity> {
+        world
+            .get::<DerivedItems>(template)
+            .map(|derived| derived.iter().collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn derived_items_follow_templates() {
+        let mut world = World::new();
+        let a = world.spawn(Item).id();
+        let b = world.spawn(Item).id();
+        let item = world.spawn((Item, Template(a))).id();
+        let nested = world.spawn((Item, Template(item))).id();
+        world.flush();
+        assert_eq!(derived(&world, a), [item]);
+        assert_eq!(
+            world.iter_descendants(a).collect::<Vec<_>>(),
+            [item, nested]
+        );
+
+        world.entity_mut(item).insert(Template(b));
+        world.flush();
+        assert!(derived(&world, a).is_empty());
+        assert_eq!(derived(&world, b), [item]);
+        assert_eq!(world.iter_descendants(a).count(), 0);
+        assert_eq!(
+            world.iter_descendants(b).collect::<Vec<_>>(),
+            [item, nested]
+        );
+
+        // Still derived through Templates after Template is removed.
+        world.entity_mut(item).insert(Templates(vec![a, b]));
+        world.entity_mut(item).remove::<Template>();
+        world.flush();
+        assert_eq!(derived(&world, a), [item]);
+        assert_eq!(derived(&world, b), [item]);
+
+        world.entity_mut(item).remove::<Templates>();
+        world.flush();
+        assert!(derived(&world, a).is_empty() && derived(&world, b).is_empty());
+        assert_eq!(world.iter_descendants(item).collect::<Vec<_>>(), [nested]);
+    }
+
+    /// Prototypes reported with [`InvalidPrototype`].
+    #[derive(Resource, Default)]
+    struct InvalidPrototypes(Vec<Entity>);
+
+    #[test]
+    fn prototypes_have_no_count() {
+        let mut world = World::new();
+        world.init_resource::<InvalidPrototypes>();
+        world.add_observer(
+            |trigger: Trigger<InvalidPrototype>, mut invalid: ResMut<InvalidPrototypes>| {
+                invalid.0.push(trigger.0);
+            },
+        );
+        let spawned = world.spawn((Item, Prototype, Count(3))).id();
+        world.flush();
+        assert!(world.get::<Count>(spawned).is_none());
+
+        world.entity_mut(spawned).insert(Count(2));
+        world.flush();
+        assert!(world.get::<Count>(spawned).is_none());
+
+        let stack = world.spawn((Item, Count(5))).id();
+        let inventory = world.spawn(Inventory(vec![Some(stack)])).id();
+        world.flush();
+        world.entity_mut(stack).insert(Prototype);
+        world.flush();
+        assert!(world.get::<Count>(stack).is_none());
+        assert_eq!(world.get::<Inventory>(inventory).unwrap().0, [None]);
+
+        assert_eq!(
+            world.resource::<InvalidPrototypes>().0,
+            [spawned, spawned, stack]
+        );
     }
 }
@@ -0,0 +1,415 @@
+//! Item templates defined in asset files.
+//!
+//! Files with the `.items.ron` extension map item ids to their definitions:
+//! ```ron
+//! {
+//!     "item::stone": (
+//!         icon: "stone.png",
+//!         components: {
+//!             "bevy_armory::components::MaxStack": (8),
+//!         },
+//!     ),
+//!     "item::mossy_stone": (
+//!         template: "item::stone",
+//!     ),
+//! }
+//! ```
+//! Every definition is spawned as a template item with its [`ItemId`] once the asset is loaded.
+//! When the asset is modified, e.g. by hot reloading, template items are updated in place
+//! and [`ItemChanged`] is emitted for them.
+
+use std::{any::TypeId, fmt};
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    ecs::{event::EventCursor, reflect::ReflectComponent},
+    prelude::*,
+    reflect::{serde::TypedReflectDeserializer, PartialReflect, TypeRegistry, TypeRegistryArc},
+    utils::HashMap,
+};
+use serde::{
+    de::{self, DeserializeSeed, Deserializer, MapAccess, Visitor},
+    Deserialize,
+};
+
+use crate::{
+    components::{Icon, Item, Template, Templates},
+    events::ItemChanged,
+    registry::{ItemId, ItemRegistry},
+};
+
+pub fn plugin(app: &mut App) {
+    app.init_asset::<ItemDefinitions>();
+    app.init_asset_loader::<ItemDefinitionsLoader>();
+    app.init_resource::<DefinedItems>();
+    // Also initialized here, since the plugin can be added without `HeadlessItemPlugin`.
+    app.init_resource::<ItemRegistry>();
+    app.add_systems(PreUpdate, spawn_definitions);
+}
+
+/// Item templates loaded from a `.items.ron` file, in the order they're defined.
+#[derive(Asset, TypePath)]
+pub struct ItemDefinitions {
+    pub items: Vec<(String, ItemDefinition)>,
+}
+
+/// Single item template.
+#[derive(TypePath)]
+pub struct ItemDefinition {
+    pub icon: Option<Handle<Image>>,
+    /// Id of the [`Template`].
+    pub template: Option<String>,
+    /// Ids of the [`Templates`].
+    pub templates: Vec<String>,
+    /// Reflected components, they have to be registered with [`ReflectComponent`].
+    pub components: Vec<Box<dyn PartialReflect>>,
+}
+
+/// Template items spawned from [`ItemDefinitions`], by their id.
+#[derive(Resource, Default)]
+pub struct DefinedItems {
+    assets: HashMap<AssetId<ItemDefinitions>, HashMap<String, Entity>>,
+    /// Reflected components inserted from the definition of each item.
+    components: HashMap<Entity, Vec<TypeId>>,
+}
+
+impl DefinedItems {
+    /// Template item with the id from any loaded definitions.
+    pub fn get(&self, id: &str) -> Option<Entity> {
+        self.assets
+            .values()
+            .find_map(|items| items.get(id).copied())
+    }
+}
+
+/// Loader of [`ItemDefinitions`].
+pub struct ItemDefinitionsLoader {
+    registry: TypeRegistryArc,
+}
+
+impl FromWorld for ItemDefinitionsLoader {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            registry: world.resource::<AppTypeRegistry>().0.clone(),
+        }
+    }
+}
+
+/// Reason why [`ItemDefinitions`] couldn't be loaded.
+#[derive(Debug)]
+pub enum ItemDefinitionsError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+}
+
+impl fmt::Display for ItemDefinitionsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "couldn't read item definitions: {error}"),
+            Self::Ron(error) => write!(f, "couldn't parse item definitions: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for ItemDefinitionsError {}
+
+impl AssetLoader for ItemDefinitionsLoader {
+    type Asset = ItemDefinitions;
+    type Settings = ();
+    type Error = ItemDefinitionsError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<ItemDefinitions, ItemDefinitionsError> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(ItemDefinitionsError::Io)?;
+        let mut deserializer =
+            ron::de::Deserializer::from_bytes(&bytes).map_err(ItemDefinitionsError::Ron)?;
+        let registry = self.registry.read();
+        let raw = DefinitionsSeed(&registry)
+            .deserialize(&mut deserializer)
+            .map_err(|error| ItemDefinitionsError::Ron(deserializer.span_error(error)))?;
+        let items = raw
+            .into_iter()
+            .map(|(id, raw)| {
+                let definition = ItemDefinition {
+                    icon: raw.icon.map(|path| load_context.load(path)),
+                    template: raw.template,
+                    templates: raw.templates,
+                    components: raw.components,
+                };
+                (id, definition)
+            })
+            .collect();
+        Ok(ItemDefinitions { items })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["items.ron"]
+    }
+}
+
+/// Definition before its assets are loaded.
+struct RawDefinition {
+    icon: Option<String>,
+    template: Option<String>,
+    templates: Vec<String>,
+    components: Vec<Box<dyn PartialReflect>>,
+}
+
+#[derive(Clone, Copy)]
+struct DefinitionsSeed<'a>(&'a TypeRegistry);
+
+impl<'de> DeserializeSeed<'de> for DefinitionsSeed<'_> {
+    type Value = Vec<(String, RawDefinition)>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for DefinitionsSeed<'_> {
+    type Value = Vec<(String, RawDefinition)>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("map of item ids to item definitions")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut items = Vec::new();
+        while let Some(id) = map.next_key::<String>()? {
+            items.push((id, map.next_value_seed(DefinitionSeed(self.0))?));
+        }
+        Ok(items)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct DefinitionSeed<'a>(&'a TypeRegistry);
+
+const FIELDS: &[&str] = &["icon", "template", "templates", "components"];
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum Field {
+    Icon,
+    Template,
+    Templates,
+    Components,
+}
+
+impl<'de> DeserializeSeed<'de> for DefinitionSeed<'_> {
+    type Value = RawDefinition;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_struct("ItemDefinition", FIELDS, self)
+    }
+}
+
+impl<'de> Visitor<'de> for DefinitionSeed<'_> {
+    type Value = RawDefinition;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("item definition")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut definition = RawDefinition {
+            icon: None,
+            template: None,
+            templates: Vec::new(),
+            components: Vec::new(),
+        };
+        while let Some(field) = map.next_key()? {
+            match field {
+                Field::Icon => definition.icon = Some(map.next_value()?),
+                Field::Template => definition.template = Some(map.next_value()?),
+                Field::Templates => definition.templates = map.next_value()?,
+                Field::Components => {
+                    definition.components = map.next_value_seed(ComponentsSeed(self.0))?
+                }
+            }
+        }
+        Ok(definition)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct ComponentsSeed<'a>(pub &'a TypeRegistry);
+
+impl<'de> DeserializeSeed<'de> for ComponentsSeed<'_> {
+    type Value = Vec<Box<dyn PartialReflect>>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ComponentsSeed<'_> {
+    type Value = Vec<Box<dyn PartialReflect>>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("map of component type paths to values")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut components = Vec::new();
+        while let Some(path) = map.next_key::<String>()? {
+            let registration = self
+                .0
+                .get_with_type_path(&path)
+                .ok_or_else(|| de::Error::custom(format!("{path} isn't registered")))?;
+            components
+                .push(map.next_value_seed(TypedReflectDeserializer::new(registration, self.0))?);
+        }
+        Ok(components)
+    }
+}
+
+/// Spawns template items of loaded [`ItemDefinitions`] and updates them when the asset is reloaded.
+fn spawn_definitions(
+    world: &mut World,
+    mut cursor: Local<EventCursor<AssetEvent<ItemDefinitions>>>,
+) {
+    let events = world.resource::<Events<AssetEvent<ItemDefinitions>>>();
+    let changed: Vec<_> = cursor
+        .read(events)
+        .filter_map(|event| match *event {
+            AssetEvent::Added { id } | AssetEvent::Modified { id } => Some(id),
+            _ => None,
+        })
+        .collect();
+    for id in changed {
+        apply_definitions(world, id);
+    }
+}
+
+/// Spawns new definitions and updates existing template items in place.
+/// Templates of removed definitions are kept alive for items based on them.
+fn apply_definitions(world: &mut World, id: AssetId<ItemDefinitions>) {
+    world.resource_scope(|world, assets: Mut<Assets<ItemDefinitions>>| {
+        let Some(definitions) = assets.get(id) else {
+            return;
+        };
+        let mut previous = world
+            .resource_mut::<DefinedItems>()
+            .assets
+            .remove(&id)
+            .unwrap_or_default();
+        let items: HashMap<_, _> = definitions
+            .items
+            .iter()
+            .map(|(item_id, _)| {
+                let item = previous.remove(item_id).unwrap_or_else(|| {
+                    let name = Name::new(item_id.clone());
+                    world.spawn((Item, ItemId(item_id.clone()), name)).id()
+                });
+                (item_id.clone(), item)
+            })
+            .collect();
+        let mut defined = world.resource_mut::<DefinedItems>();
+        defined.assets.insert(id, items.clone());
+        for item in previous.values() {
+            defined.components.remove(item);
+        }
+
+        let registry = world.resource::<AppTypeRegistry>().clone();
+        let registry = registry.read();
+        for (item_id, definition) in &definitions.items {
+            let item = items[item_id];
+            apply_definition(world, &registry, &items, item, item_id, definition);
+        }
+        for &item in items.values() {
+            world.trigger_targets(ItemChanged, item);
+        }
+    });
+}
+
+/// Inserts data of the definition into its template item,
+/// removing data of the previous definition which is no longer defined.
+fn apply_definition(
+    world: &mut World,
+    registry: &TypeRegistry,
+    items: &HashMap<String, Entity>,
+    item: Entity,
+    item_id: &str,
+    definition: &ItemDefinition,
+) {
+    let resolve = |world: &World, id: &String| {
+        let template = items
+            .get(id)
+            .copied()
+            .or_else(|| world.resource::<ItemRegistry>().get(id));
+        if template.is_none() {
+            warn!("template {id} of {item_id} isn't defined");
+        }
+        template
+    };
+    let template = definition
+        .template
+        .as_ref()
+        .and_then(|id| resolve(world, id));
+    let templates: Vec<_> = definition
+        .templates
+        .iter()
+        .filter_map(|id| resolve(world, id))
+        .collect();
+    let previous = world
+        .resource_mut::<DefinedItems>()
+        .components
+        .remove(&item)
+        .unwrap_or_default();
+
+    let mut entity = world.entity_mut(item);
+    match &definition.icon {
+        Some(icon) => {
+            entity.insert(Icon(icon.clone()));
+        }
+        None => {
+            entity.remove::<Icon>();
+        }
+    }
+    let mut applied = Vec::new();
+    for component in &definition.components {
+        let reflect_component = component
+            .get_represented_type_info()
+            .and_then(|info| registry.get_type_data::<ReflectComponent>(info.type_id()));
+        let Some(reflect_component) = reflect_component else {
+            warn!(
+                "component {} of {item_id} doesn't reflect Component",
+                component.reflect_type_path()
+            );
+            continue;
+        };
+        reflect_component.insert(&mut entity, component.as_partial_reflect(), registry);
+        applied.push(component.get_represented_type_info().unwrap().type_id());
+    }
+    for type_id in previous.iter().filter(|t| !applied.contains(t)) {
+        if let Some(reflect_component) = registry.get_type_data::<ReflectComponent>(*type_id) {
+            reflect_component.remove(&mut entity);
+        }
+    }
+    match template {
+        Some(template) => {
+            entity.insert(Template(template));
+        }
+        None => {
+            entity.remove::<Template>();
+        }
+    }
+    if templates.is_empty() {
+        entity.remove::<Templates>();
+    } else {
+        entity.insert(Templates(templates));
+    }
+    world
+        .resource_mut::<DefinedItems>()
+        .components
+        .insert(item, applied);
+}
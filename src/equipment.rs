@@ -0,0 +1,214 @@
+//! Inventories with named slots.
+
+use bevy::{
+    ecs::{component::ComponentId, query::QueryData, world::DeferredWorld},
+    prelude::*,
+};
+
+use crate::{
+    components::Inventory,
+    events::InventoryChanged,
+    filter::{ItemFilter, SlotFilters},
+};
+
+pub fn plugin(app: &mut App) {
+    app.add_observer(equipment_changed);
+}
+
+/// Named slot of an [`Equipment`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EquipmentSlot {
+    Head,
+    Chest,
+    MainHand,
+    OffHand,
+    Ring1,
+    Ring2,
+    /// Game specific slot.
+    Custom(&'static str),
+}
+
+/// Maps named slots onto indices of the [`Inventory`] on the same entity.
+#[derive(Component)]
+#[component(on_add = equipment_added)]
+pub struct Equipment {
+    slots: Vec<EquipmentSlot>,
+    /// Items equipped as of the last [`InventoryChanged`].
+    equipped: Vec<Option<Entity>>,
+}
+
+impl Default for Equipment {
+    fn default() -> Self {
+        Self::new([
+            EquipmentSlot::Head,
+            EquipmentSlot::Chest,
+            EquipmentSlot::MainHand,
+            EquipmentSlot::OffHand,
+            EquipmentSlot::Ring1,
+            EquipmentSlot::Ring2,
+        ])
+    }
+}
+
+impl Equipment {
+    /// Creates a layout where slots are mapped onto consecutive indices.
+    pub fn new(slots: impl IntoIterator<Item = EquipmentSlot>) -> Self {
+        let slots: Vec<_> = slots.into_iter().collect();
+        let equipped = vec![None; slots.len()];
+        Self { slots, equipped }
+    }
+
+    /// Inventory index of the slot.
+    pub fn index(&self, slot: EquipmentSlot) -> Option<usize> {
+        self.slots.iter().position(|&s| s == slot)
+    }
+
+    /// Slot at the inventory index.
+    pub fn slot(&self, index: usize) -> Option<EquipmentSlot> {
+        self.slots.get(index).copied()
+    }
+
+    /// All slots with their inventory indices.
+    pub fn slots(&self) -> impl Iterator<Item = (usize, EquipmentSlot)> + '_ {
+        self.slots.iter().copied().enumerate()
+    }
+
+    /// Empty inventory matching this layout.
+    pub fn inventory(&self) -> Inventory {
+        Inventory::with_size(self.slots.len())
+    }
+
+    /// Slot filters for named slots.
+    pub fn filters(
+        &self,
+        filters: impl IntoIterator<Item = (EquipmentSlot, ItemFilter)>,
+    ) -> SlotFilters {
+        filters
+            .into_iter()
+            .filter_map(|(slot, filter)| Some((self.index(slot)?, filter)))
+            .fold(SlotFilters::default(), |filters, (index, filter)| {
+                filters.with(index, filter)
+            })
+    }
+}
+
+/// Query for accessing equipment by named slots.
+#[derive(QueryData)]
+pub struct EquipmentData {
+    pub equipment: &'static Equipment,
+    pub inventory: &'static Inventory,
+}
+
+impl EquipmentDataItem<'_> {
+    /// Item in the named slot.
+    pub fn get(&self, slot: EquipmentSlot) -> Option<Entity> {
+        let index = self.equipment.index(slot)?;
+        self.inventory.0.get(index).copied().flatten()
+    }
+}
+
+/// Event emitted on the equipment entity when an item is put into a named slot.
+#[derive(Event)]
+pub struct ItemEquipped {
+    pub slot: EquipmentSlot,
+    pub item: Entity,
+}
+
+/// Event emitted on the equipment entity when an item is taken out of a named slot.
+#[derive(Event)]
+pub struct ItemUnequipped {
+    pub slot: EquipmentSlot,
+    pub item: Entity,
+}
+
+/// Emits events for items which were already equipped when the equipment was added.
+fn equipment_added(mut world: DeferredWorld, entity: Entity, _: ComponentId) {
+    world.commands().trigger_targets(InventoryChanged, entity);
+}
+
+fn equipment_changed(
+    trigger: Trigger<InventoryChanged>,
+    mut equipment: Query<(&mut Equipment, &Inventory)>,
+    mut commands: Commands,
+) {
+    let entity = trigger.entity();
+    let Ok((mut equipment, inventory)) = equipment.get_mut(entity) else {
+        return;
+    };
+    let equipment = &mut *equipment;
+    for (index, (&slot, equipped)) in equipment
+        .slots
+        .iter()
+        .zip(equipment.equipped.iter_mut())
+        .enumerate()
+    {
+        let current = inventory.0.get(index).copied().flatten();
+        if *equipped == current {
+            continue;
+        }
+        if let Some(item) = *equipped {
+            commands.trigger_targets(ItemUnequipped { slot, item }, entity);
+        }
+        if let Some(item) = current {
+            commands.trigger_targets(ItemEquipped { slot, item }, entity);
+        }
+        *equipped = current;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{components::Item, ops::InventoryWorldExt};
+
+    /// Equip events in the order they were emitted, `true` for [`ItemEquipped`].
+    #[derive(Resource, Default)]
+    struct EquipLog(Vec<(bool, EquipmentSlot, Entity)>);
+
+    #[test]
+    fn equip_events() {
+        let mut world = World::new();
+        world.init_resource::<EquipLog>();
+        world.add_observer(equipment_changed);
+        world.add_observer(
+            |trigger: Trigger<ItemEquipped>, mut events: ResMut<EquipLog>| {
+                events.0.push((true, trigger.slot, trigger.item));
+            },
+        );
+        world.add_observer(
+            |trigger: Trigger<ItemUnequipped>, mut events: ResMut<EquipLog>| {
+                events.0.push((false, trigger.slot, trigger.item));
+            },
+        );
+        let sword = world.spawn(Item).id();
+        let bag = world.spawn(Inventory(vec![Some(sword)])).id();
+        let equipment = Equipment::new([EquipmentSlot::Head, EquipmentSlot::MainHand]);
+        let character = world.spawn((equipment.inventory(), equipment)).id();
+        world.flush();
+        assert!(world.resource::<EquipLog>().0.is_empty());
+
+        world
+            .inventory_ops()
+            .move_item(bag, 0, character, 1)
+            .unwrap();
+        world.flush();
+        assert_eq!(
+            world
+                .resource_mut::<EquipLog>()
+                .0
+                .drain(..)
+                .collect::<Vec<_>>(),
+            [(true, EquipmentSlot::MainHand, sword)]
+        );
+
+        world
+            .inventory_ops()
+            .move_item(character, 1, bag, 0)
+            .unwrap();
+        world.flush();
+        assert_eq!(
+            world.resource::<EquipLog>().0,
+            [(false, EquipmentSlot::MainHand, sword)]
+        );
+    }
+}
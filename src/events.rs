@@ -0,0 +1,43 @@
+//! Events emitted by items and inventories.
+
+use bevy::prelude::*;
+
+use crate::{ops::InventoryError, params::TemplateError};
+
+/// Event emitted when contents of an inventory change.
+#[derive(Event)]
+pub struct InventoryChanged;
+
+/// Event emitted when item descriptor changes.
+/// It's also emitted if any of the extended items change.
+#[derive(Event)]
+pub struct ItemChanged;
+
+/// Event emitted when an item of a removed inventory is kept without a slot,
+/// see [`ContentsPolicy`](crate::lifecycle::ContentsPolicy).
+#[derive(Event)]
+pub struct ItemDropped {
+    /// Inventory the item was in.
+    pub inventory: Entity,
+}
+
+/// Event emitted when an inventory operation fails.
+#[derive(Event)]
+pub struct InventoryOperationFailed(pub InventoryError);
+
+/// Event emitted when a [`Template`](crate::components::Template) is rejected and removed.
+#[derive(Event)]
+pub struct InvalidTemplate(pub TemplateError);
+
+/// Event emitted when a [`Count`](crate::components::Count) is removed from a [`Prototype`](crate::components::Prototype).
+#[derive(Event)]
+pub struct InvalidPrototype(pub Entity);
+
+/// Event emitted when an [`ItemId`](crate::registry::ItemId) is already used by another item.
+/// The id stays registered to the first item.
+#[derive(Event)]
+pub struct DuplicateItemId {
+    pub id: String,
+    pub registered: Entity,
+    pub duplicate: Entity,
+}
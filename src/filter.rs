@@ -0,0 +1,129 @@
+//! Rules deciding which items can be put into which slots.
+
+use std::{any::type_name, borrow::Cow};
+
+use bevy::{ecs::system::SystemState, prelude::*};
+
+use crate::params::ItemData;
+
+/// Rule deciding whether an item can be put into a slot.
+#[derive(Clone)]
+pub struct ItemFilter {
+    /// Reason reported when an item is rejected.
+    pub reason: Cow<'static, str>,
+    predicate: fn(&mut World, Entity) -> bool,
+}
+
+impl ItemFilter {
+    pub fn new(
+        reason: impl Into<Cow<'static, str>>,
+        predicate: fn(&mut World, Entity) -> bool,
+    ) -> Self {
+        Self {
+            reason: reason.into(),
+            predicate,
+        }
+    }
+
+    /// Accepts items which have or inherit component `C`.
+    pub fn with<C: Component>() -> Self {
+        Self::new(format!("requires {}", short_name::<C>()), has::<C>)
+    }
+
+    /// Accepts items which neither have nor inherit component `C`.
+    pub fn without<C: Component>() -> Self {
+        Self::new(format!("forbids {}", short_name::<C>()), |world, item| {
+            !has::<C>(world, item)
+        })
+    }
+
+    pub fn accepts(&self, world: &mut World, item: Entity) -> bool {
+        (self.predicate)(world, item)
+    }
+}
+
+fn has<C: Component>(world: &mut World, item: Entity) -> bool {
+    let mut state = SystemState::<ItemData<&C>>::new(world);
+    let items = state.get(world);
+    matches!(items.extended_get(item), Ok(Some(_)))
+}
+
+fn short_name<C>() -> &'static str {
+    let name = type_name::<C>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// Filters applied to every slot of the inventory.
+#[derive(Component, Clone, Default)]
+pub struct InventoryFilter(pub Vec<ItemFilter>);
+
+/// Filters applied to individual slots of the inventory, indexed by slot.
+#[derive(Component, Clone, Default)]
+pub struct SlotFilters(pub Vec<Option<ItemFilter>>);
+
+impl SlotFilters {
+    /// Sets the filter of a single slot.
+    pub fn with(mut self, index: usize, filter: ItemFilter) -> Self {
+        if self.0.len() <= index {
+            self.0.resize(index + 1, None);
+        }
+        self.0[index] = Some(filter);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        components::{Inventory, Item, MaxStack, Template},
+        ops::{InventoryError, InventoryWorldExt},
+    };
+
+    #[test]
+    fn rejected_move_keeps_slots() {
+        let mut world = World::new();
+        let item = world.spawn(Item).id();
+        let inventory = world.spawn(Inventory(vec![Some(item)])).id();
+        let filtered = world
+            .spawn((
+                Inventory::with_size(1),
+                InventoryFilter(vec![ItemFilter::with::<MaxStack>()]),
+            ))
+            .id();
+        world.flush();
+
+        let result = world.inventory_ops().move_item(inventory, 0, filtered, 0);
+
+        assert_eq!(
+            result,
+            Err(InventoryError::RuleRejected {
+                item,
+                reason: "requires MaxStack".into()
+            })
+        );
+        assert_eq!(world.get::<Inventory>(inventory).unwrap().0, [Some(item)]);
+        assert_eq!(world.get::<Inventory>(filtered).unwrap().0, [None]);
+    }
+
+    #[test]
+    fn slot_filters_accept_inherited() {
+        let mut world = World::new();
+        let template = world.spawn((Item, MaxStack(5))).id();
+        let item = world.spawn((Item, Template(template))).id();
+        let plain = world.spawn(Item).id();
+        let inventory = world
+            .spawn((
+                Inventory::with_size(2),
+                SlotFilters::default().with(0, ItemFilter::with::<MaxStack>()),
+            ))
+            .id();
+        world.flush();
+        let mut ops = world.inventory_ops();
+
+        assert!(ops.accepts(inventory, 0, item).is_ok());
+        assert!(ops.accepts(inventory, 0, plain).is_err());
+        assert!(ops.accepts(inventory, 1, plain).is_ok());
+        assert_eq!(ops.first_free_slot_for(inventory, plain), Ok(Some(1)));
+    }
+}
@@ -0,0 +1,347 @@
+//! Inventories where items occupy multiple cells of a grid.
+
+use bevy::{ecs::system::SystemState, prelude::*};
+
+use crate::{components::Inventory, ops::InventoryError, params::ItemData};
+
+/// Cells an item occupies in a [`GridInventory`], relative to the cell it's anchored in.
+/// Items without a shape occupy a single cell.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub enum Shape {
+    Rect {
+        width: u32,
+        height: u32,
+    },
+    /// Row-major mask of occupied cells.
+    Mask {
+        width: u32,
+        cells: Vec<bool>,
+    },
+}
+
+impl Shape {
+    /// Size of the bounding box.
+    pub fn size(&self, rotated: bool) -> UVec2 {
+        let size = match self {
+            Self::Rect { width, height } => UVec2::new(*width, *height),
+            Self::Mask { width, cells } => {
+                UVec2::new(*width, (cells.len() as u32).div_ceil((*width).max(1)))
+            }
+        };
+        if rotated {
+            size.yx()
+        } else {
+            size
+        }
+    }
+
+    /// Offsets of occupied cells from the anchor cell.
+    /// Shapes with a zero width, e.g. from malformed definitions, occupy no cells.
+    pub fn cells(&self, rotated: bool) -> Vec<UVec2> {
+        let cells: Vec<_> = match self {
+            Self::Rect { width, height } => (0..*height)
+                .flat_map(|y| (0..*width).map(move |x| UVec2::new(x, y)))
+                .collect(),
+            Self::Mask { width: 0, .. } => Vec::new(),
+            Self::Mask { width, cells } => cells
+                .iter()
+                .enumerate()
+                .filter(|(_, &occupied)| occupied)
+                .map(|(i, _)| UVec2::new(i as u32 % width, i as u32 / width))
+                .collect(),
+        };
+        if !rotated {
+            return cells;
+        }
+        // Rotate clockwise by 90 degrees.
+        let height = self.size(false).y;
+        cells
+            .into_iter()
+            .map(|cell| UVec2::new(height - 1 - cell.y, cell.x))
+            .collect()
+    }
+}
+
+/// Rotates the item's [`Shape`] by 90 degrees in grid inventories.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component, Default)]
+pub struct Rotated;
+
+/// Lays out the [`Inventory`] on the same entity as a grid, slots are indexed row by row.
+/// Items are anchored at the slot they're in and occupy the cells of their [`Shape`].
+#[derive(Component, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct GridInventory {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl GridInventory {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+
+    /// Empty inventory with a slot for every cell.
+    pub fn inventory(&self) -> Inventory {
+        Inventory::with_size((self.width * self.height) as usize)
+    }
+
+    /// Cell of the slot index.
+    /// Grids with a zero width have no cells, so the cell is outside of the grid.
+    pub fn cell(&self, index: usize) -> UVec2 {
+        let width = self.width.max(1);
+        UVec2::new(index as u32 % width, index as u32 / width)
+    }
+
+    /// Slot index of the cell, [`None`] if it's outside of the grid.
+    pub fn index(&self, cell: UVec2) -> Option<usize> {
+        (cell.x < self.width && cell.y < self.height)
+            .then_some((cell.y * self.width + cell.x) as usize)
+    }
+}
+
+/// Cells occupied by an item anchored at the slot index, [`None`] if it doesn't fit in the grid.
+/// Shapes without any cells never fit.
+fn item_cells(
+    world: &World,
+    shapes: &ItemData<&Shape>,
+    grid: GridInventory,
+    index: usize,
+    item: Entity,
+) -> Option<Vec<usize>> {
+    anchor_cells(grid, index, &item_offsets(world, shapes, item))
+}
+
+/// Offsets of cells occupied by the item, see [`Shape::cells`].
+fn item_offsets(world: &World, shapes: &ItemData<&Shape>, item: Entity) -> Vec<UVec2> {
+    let rotated = world.get::<Rotated>(item).is_some();
+    match shapes.extended_get(item) {
+        Ok(Some(shape)) => shape.cells(rotated),
+        _ => vec![UVec2::ZERO],
+    }
+}
+
+/// Cells of the offsets anchored at the slot index, [`None`] if they don't fit in the grid.
+fn anchor_cells(grid: GridInventory, index: usize, offsets: &[UVec2]) -> Option<Vec<usize>> {
+    if offsets.is_empty() {
+        return None;
+    }
+    let anchor = grid.cell(index);
+    offsets
+        .iter()
+        .map(|&offset| grid.index(anchor + offset))
+        .collect()
+}
+
+/// Anchor slot of the item covering each cell, [`None`] if the inventory isn't a grid.
+pub fn occupancy(world: &mut World, inventory: Entity) -> Option<Vec<Option<usize>>> {
+    let grid = *world.get::<GridInventory>(inventory)?;
+    let slots = world.get::<Inventory>(inventory)?.0.clone();
+    let mut state = SystemState::<ItemData<&Shape>>::new(world);
+    let shapes = state.get(world);
+    Some(occupied_by(world, &shapes, grid, &slots))
+}
+
+fn occupied_by(
+    world: &World,
+    shapes: &ItemData<&Shape>,
+    grid: GridInventory,
+    slots: &[Option<Entity>],
+) -> Vec<Option<usize>> {
+    let mut occupancy = vec![None; slots.len()];
+    for (index, item) in slots.iter().enumerate() {
+        let Some(item) = *item else {
+            continue;
+        };
+        for cell in item_cells(world, shapes, grid, index, item).unwrap_or_default() {
+            if let Some(occupied) = occupancy.get_mut(cell) {
+                *occupied = Some(index);
+            }
+        }
+    }
+    occupancy
+}
+
+/// Whether the item fits into the grid when anchored at the slot index.
+/// Always true for inventories which aren't grids.
+pub fn fits(world: &mut World, inventory: Entity, index: usize, item: Entity) -> bool {
+    let Some(occupancy) = occupancy(world, inventory) else {
+        return true;
+    };
+    let grid = *world.get::<GridInventory>(inventory).unwrap();
+    let mut state = SystemState::<ItemData<&Shape>>::new(world);
+    let shapes = state.get(world);
+    let Some(cells) = item_cells(world, &shapes, grid, index, item) else {
+        return false;
+    };
+    cells
+        .into_iter()
+        .all(|cell| occupancy.get(cell).is_some_and(Option::is_none))
+}
+
+/// Whether the item fits into the grid when anchored at each slot, [`None`] if the inventory isn't a grid.
+/// Unlike calling [`fits`] for every slot, the occupancy is computed only once.
+pub fn fitting_slots(world: &mut World, inventory: Entity, item: Entity) -> Option<Vec<bool>> {
+    let grid = *world.get::<GridInventory>(inventory)?;
+    let slots = world.get::<Inventory>(inventory)?.0.clone();
+    let mut state = SystemState::<ItemData<&Shape>>::new(world);
+    let shapes = state.get(world);
+    let occupancy = occupied_by(world, &shapes, grid, &slots);
+    let offsets = item_offsets(world, &shapes, item);
+    let fitting = (0..occupancy.len())
+        .map(|index| {
+            anchor_cells(grid, index, &offsets).is_some_and(|cells| {
+                cells
+                    .into_iter()
+                    .all(|cell| occupancy.get(cell).is_some_and(Option::is_none))
+            })
+        })
+        .collect();
+    Some(fitting)
+}
+
+/// Checks that no item overlaps another item or the edge of the grid.
+pub fn validate(world: &mut World, inventory: Entity) -> Result<(), InventoryError> {
+    let Some(&grid) = world.get::<GridInventory>(inventory) else {
+        return Ok(());
+    };
+    let Some(slots) = world.get::<Inventory>(inventory).map(|i| i.0.clone()) else {
+        return Ok(());
+    };
+    let mut state = SystemState::<ItemData<&Shape>>::new(world);
+    let shapes = state.get(world);
+    let mut occupied = vec![false; slots.len()];
+    for (index, item) in slots.iter().enumerate() {
+        let Some(item) = *item else {
+            continue;
+        };
+        let does_not_fit = InventoryError::DoesNotFit { inventory, index };
+        let cells = item_cells(world, &shapes, grid, index, item).ok_or(does_not_fit.clone())?;
+        for cell in cells {
+            let occupied = occupied.get_mut(cell).ok_or(does_not_fit.clone())?;
+            if *occupied {
+                return Err(does_not_fit);
+            }
+            *occupied = true;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{components::Item, ops::InventoryWorldExt};
+
+    fn grid(world: &mut World, width: u32, height: u32) -> Entity {
+        let grid = GridInventory::new(width, height);
+        let inventory = world.spawn((grid.inventory(), grid)).id();
+        world.flush();
+        inventory
+    }
+
+    #[test]
+    fn overlapping_items_do_not_fit() {
+        let mut world = World::new();
+        let inventory = grid(&mut world, 3, 2);
+        let wide = Shape::Rect {
+            width: 2,
+            height: 1,
+        };
+        let a = world.spawn((Item, wide.clone())).id();
+        let b = world.spawn((Item, wide)).id();
+        world.inventory_ops().insert(inventory, 0, a).unwrap();
+
+        let overlapping = world.inventory_ops().insert(inventory, 1, b);
+        let outside = world.inventory_ops().insert(inventory, 2, b);
+
+        assert_eq!(
+            overlapping,
+            Err(InventoryError::DoesNotFit {
+                inventory,
+                index: 1
+            })
+        );
+        assert_eq!(
+            outside,
+            Err(InventoryError::DoesNotFit {
+                inventory,
+                index: 2
+            })
+        );
+        let slots = &world.get::<Inventory>(inventory).unwrap().0;
+        assert_eq!(slots[..3], [Some(a), None, None]);
+        assert_eq!(
+            occupancy(&mut world, inventory).unwrap()[..3],
+            [Some(0), Some(0), None]
+        );
+        assert_eq!(
+            world.inventory_ops().first_free_slot_for(inventory, b),
+            Ok(Some(3))
+        );
+    }
+
+    #[test]
+    fn rotation() {
+        let l_shape = Shape::Mask {
+            width: 2,
+            cells: vec![true, false, true, true],
+        };
+        assert_eq!(
+            l_shape.cells(true),
+            [UVec2::new(1, 0), UVec2::new(0, 0), UVec2::new(0, 1)]
+        );
+        assert_eq!(l_shape.size(true), UVec2::new(2, 2));
+
+        let mut world = World::new();
+        let inventory = grid(&mut world, 2, 1);
+        let tall = Shape::Rect {
+            width: 1,
+            height: 2,
+        };
+        let item = world.spawn((Item, tall, Rotated)).id();
+        world.inventory_ops().insert(inventory, 0, item).unwrap();
+
+        // Upright it doesn't fit, so rotating is rolled back.
+        let rotated = world.inventory_ops().rotate(inventory, 0);
+
+        assert_eq!(
+            rotated,
+            Err(InventoryError::DoesNotFit {
+                inventory,
+                index: 0
+            })
+        );
+        assert!(world.get::<Rotated>(item).is_some());
+    }
+
+    #[test]
+    fn zero_width() {
+        let empty = Shape::Mask {
+            width: 0,
+            cells: vec![true, true],
+        };
+        assert!(empty.cells(false).is_empty());
+        assert!(empty.cells(true).is_empty());
+
+        let mut world = World::new();
+        let inventory = grid(&mut world, 2, 2);
+        let item = world.spawn((Item, empty)).id();
+        assert!(!fits(&mut world, inventory, 0, item));
+        assert_eq!(
+            world.inventory_ops().first_free_slot_for(inventory, item),
+            Ok(None)
+        );
+
+        let degenerate = GridInventory::new(0, 3);
+        assert_eq!(degenerate.index(degenerate.cell(2)), None);
+        let mut slots = Inventory::with_size(2);
+        let single = world.spawn(Item).id();
+        slots.0[1] = Some(single);
+        let inventory = world.spawn((slots, degenerate)).id();
+        world.flush();
+        assert_eq!(occupancy(&mut world, inventory), Some(vec![None, None]));
+        assert!(validate(&mut world, inventory).is_err());
+    }
+}
@@ -0,0 +1,342 @@
+//! Undo and redo of inventory operations.
+
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::{
+    events::InventoryOperationFailed,
+    ops::{Change, InventoryOps, InventoryWorldExt},
+    ownership,
+};
+
+/// Invertible record of a single applied transaction.
+pub struct InventoryRecord {
+    pub(crate) changes: Vec<Change>,
+    /// Inventories affected by the transaction.
+    pub inventories: Vec<Entity>,
+    /// Items affected by the transaction.
+    pub items: Vec<Entity>,
+}
+
+impl InventoryRecord {
+    /// Appends a later record, so both are undone as a single step.
+    fn extend(&mut self, record: InventoryRecord) {
+        self.changes.extend(record.changes);
+        for inventory in record.inventories {
+            if !self.inventories.contains(&inventory) {
+                self.inventories.push(inventory);
+            }
+        }
+        for item in record.items {
+            if !self.items.contains(&item) {
+                self.items.push(item);
+            }
+        }
+    }
+
+    /// Items which are detached while this record is applied.
+    fn despawned(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.changes.iter().filter_map(|change| match *change {
+            Change::Despawn(item) => Some(item),
+            _ => None,
+        })
+    }
+
+    /// Items which are detached while this record is reverted.
+    fn spawned(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.changes.iter().filter_map(|change| match *change {
+            Change::Spawn(item) => Some(item),
+            _ => None,
+        })
+    }
+
+    /// Marks the affected items as changed.
+    fn items_changed(&self, ops: &mut InventoryOps) {
+        for &item in &self.items {
+            ops.item_changed(item);
+        }
+    }
+}
+
+/// Despawns detached items of a record dropped from the history.
+fn despawn_orphans(world: &mut World, orphans: impl IntoIterator<Item = Entity>) {
+    for orphan in orphans {
+        if ownership::contained_in(world, orphan).is_some() {
+            continue;
+        }
+        if let Ok(orphan) = world.get_entity_mut(orphan) {
+            orphan.despawn_recursive();
+        }
+    }
+}
+
+/// Temporary inventory whose slots aren't recorded into the [`InventoryHistory`],
+/// e.g. the carrier of items dragged by a pointer.
+/// Transactions are merged into a single record while any of these inventories holds items,
+/// so moving an item through them is undone in one step.
+#[derive(Component, Default)]
+pub struct UntrackedInventory;
+
+/// Optional history of applied inventory operations.
+/// When present, every transaction is recorded and can be undone with [`UndoInventory`].
+///
+/// Items emptied by merging are kept alive while they can be restored,
+/// they're despawned once their record is dropped from the history.
+#[derive(Resource)]
+pub struct InventoryHistory {
+    undo: VecDeque<InventoryRecord>,
+    redo: Vec<InventoryRecord>,
+    /// Maximum amount of records which can be undone.
+    pub capacity: usize,
+    /// Whether the next record is merged into the last one.
+    open: bool,
+}
+
+impl Default for InventoryHistory {
+    fn default() -> Self {
+        Self::new(128)
+    }
+}
+
+impl InventoryHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            capacity,
+            open: false,
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Records an applied transaction, clearing the redo stack.
+    /// While the history is `open` the record is merged into the last one.
+    /// Returns detached items which can no longer be restored.
+    pub(crate) fn record(&mut self, record: InventoryRecord, open: bool) -> Vec<Entity> {
+        let merge = std::mem::replace(&mut self.open, open);
+        if record.changes.is_empty() {
+            return Vec::new();
+        }
+        if let Some(last) = self.undo.back_mut().filter(|_| merge) {
+            last.extend(record);
+            return Vec::new();
+        }
+        let mut orphans = Vec::new();
+        for record in self.redo.drain(..) {
+            orphans.extend(record.spawned());
+        }
+        self.undo.push_back(record);
+        while self.undo.len() > self.capacity {
+            let oldest = self.undo.pop_front().unwrap();
+            orphans.extend(oldest.despawned());
+        }
+        orphans
+    }
+}
+
+/// Command reverting the last recorded inventory transaction.
+///
+/// Changes are applied as a transaction with all checks of regular operations.
+/// If slots were changed outside of the history in the meantime, the record is dropped
+/// and the failure is reported with [`InventoryOperationFailed`].
+pub struct UndoInventory;
+
+impl Command for UndoInventory {
+    fn apply(self, world: &mut World) {
+        let Some(record) = world
+            .get_resource_mut::<InventoryHistory>()
+            .and_then(|mut history| {
+                history.open = false;
+                history.undo.pop_back()
+            })
+        else {
+            return;
+        };
+        let result = world.inventory_ops().untracked_transaction(|ops| {
+            for change in record.changes.iter().rev() {
+                ops.revert(change)?;
+            }
+            record.items_changed(ops);
+            Ok(())
+        });
+        match result {
+            Ok(()) => world.resource_mut::<InventoryHistory>().redo.push(record),
+            Err(error) => {
+                // Items emptied by the transaction can no longer be restored.
+                despawn_orphans(world, record.despawned().collect::<Vec<_>>());
+                world.trigger(InventoryOperationFailed(error));
+            }
+        }
+    }
+}
+
+/// Command reapplying the last undone inventory transaction.
+/// Outdated records are dropped like in [`UndoInventory`].
+pub struct RedoInventory;
+
+impl Command for RedoInventory {
+    fn apply(self, world: &mut World) {
+        let Some(record) = world
+            .get_resource_mut::<InventoryHistory>()
+            .and_then(|mut history| {
+                history.open = false;
+                history.redo.pop()
+            })
+        else {
+            return;
+        };
+        let result = world.inventory_ops().untracked_transaction(|ops| {
+            for change in &record.changes {
+                ops.reapply(change)?;
+            }
+            record.items_changed(ops);
+            Ok(())
+        });
+        match result {
+            Ok(()) => world
+                .resource_mut::<InventoryHistory>()
+                .undo
+                .push_back(record),
+            Err(error) => {
+                // Items split off by the transaction can no longer be restored.
+                despawn_orphans(world, record.spawned().collect::<Vec<_>>());
+                world.trigger(InventoryOperationFailed(error));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::{Count, Inventory, Item, Template};
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.init_resource::<InventoryHistory>();
+        world
+    }
+
+    /// Template with stacks of the given counts in an inventory with an extra free slot.
+    fn stacks(world: &mut World, counts: &[u32]) -> (Entity, Vec<Entity>) {
+        let template = world.spawn(Item).id();
+        let items: Vec<_> = counts
+            .iter()
+            .map(|&count| world.spawn((Item, Template(template), Count(count))).id())
+            .collect();
+        let mut slots: Vec<_> = items.iter().copied().map(Some).collect();
+        slots.push(None);
+        let inventory = world.spawn(Inventory(slots)).id();
+        world.flush();
+        (inventory, items)
+    }
+
+    fn state(world: &World, inventory: Entity) -> Vec<Option<(Entity, u32)>> {
+        let slots = &world.get::<Inventory>(inventory).unwrap().0;
+        slots
+            .iter()
+            .map(|slot| slot.map(|item| (item, world.get::<Count>(item).unwrap().0)))
+            .collect()
+    }
+
+    #[test]
+    fn undo_redo_round_trip() {
+        let mut world = world();
+        let (inventory, items) = stacks(&mut world, &[4, 6]);
+        let initial = state(&world, inventory);
+
+        world
+            .inventory_ops()
+            .merge(inventory, 0, inventory, 1, 4)
+            .unwrap();
+        let merged = state(&world, inventory);
+        world
+            .inventory_ops()
+            .split(inventory, 1, inventory, 0, 3)
+            .unwrap();
+        let split = state(&world, inventory);
+        world
+            .inventory_ops()
+            .swap(inventory, 0, inventory, 2)
+            .unwrap();
+        let swapped = state(&world, inventory);
+
+        UndoInventory.apply(&mut world);
+        assert_eq!(state(&world, inventory), split);
+        UndoInventory.apply(&mut world);
+        assert_eq!(state(&world, inventory), merged);
+        UndoInventory.apply(&mut world);
+        assert_eq!(state(&world, inventory), initial);
+        assert!(!world.resource::<InventoryHistory>().can_undo());
+
+        RedoInventory.apply(&mut world);
+        assert_eq!(state(&world, inventory), merged);
+        assert!(world.get_entity(items[0]).is_ok());
+        RedoInventory.apply(&mut world);
+        assert_eq!(state(&world, inventory), split);
+        RedoInventory.apply(&mut world);
+        assert_eq!(state(&world, inventory), swapped);
+        assert!(!world.resource::<InventoryHistory>().can_redo());
+    }
+
+    #[test]
+    fn outdated_record_is_dropped() {
+        let mut world = world();
+        let (inventory, items) = stacks(&mut world, &[4, 6]);
+        world
+            .inventory_ops()
+            .merge(inventory, 0, inventory, 1, 4)
+            .unwrap();
+        // Emptied stack is kept alive while the merge can be undone.
+        assert!(world.get_entity(items[0]).is_ok());
+        let other = world.spawn(Item).id();
+        world.get_mut::<Inventory>(inventory).unwrap().0[0] = Some(other);
+
+        UndoInventory.apply(&mut world);
+
+        let history = world.resource::<InventoryHistory>();
+        assert!(!history.can_undo() && !history.can_redo());
+        assert!(world.get_entity(items[0]).is_err());
+        assert_eq!(
+            world.get::<Inventory>(inventory).unwrap().0,
+            [Some(other), Some(items[1]), None]
+        );
+    }
+
+    #[test]
+    fn untracked_inventory_merges_records() {
+        let mut world = world();
+        let (inventory, items) = stacks(&mut world, &[4]);
+        let carrier = world
+            .spawn((Inventory::with_size(1), UntrackedInventory))
+            .id();
+        world.flush();
+
+        world
+            .inventory_ops()
+            .move_item(inventory, 0, carrier, 0)
+            .unwrap();
+        world
+            .inventory_ops()
+            .move_item(carrier, 0, inventory, 1)
+            .unwrap();
+        world.entity_mut(carrier).despawn();
+
+        UndoInventory.apply(&mut world);
+
+        assert_eq!(
+            world.get::<Inventory>(inventory).unwrap().0,
+            [Some(items[0]), None]
+        );
+        let history = world.resource::<InventoryHistory>();
+        assert!(!history.can_undo() && history.can_redo());
+    }
+}
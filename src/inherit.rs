@@ -0,0 +1,293 @@
+//! Copying of inheritable components from templates onto derived items
+//! and propagation of template changes.
+
+use std::any::TypeId;
+
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{
+    components::{DerivedItems, DescendantsExt, Icon, Item, MaxStack, Template, Templates},
+    events::ItemChanged,
+    grid::Shape,
+    params::resolution_order,
+    transaction::InventorySystem,
+};
+
+pub fn plugin(app: &mut App) {
+    app.init_resource::<InheritableComponents>();
+    app.register_inheritable::<Icon>()
+        .register_inheritable::<MaxStack>()
+        .register_inheritable::<Shape>();
+    app.add_observer(auto_flatten::<Template>);
+    app.add_observer(auto_flatten::<Templates>);
+    app.add_observer(forward_item_changed);
+}
+
+/// Registry of components copied from templates by [`FlattenTemplates`].
+/// Changes of these components on templates are propagated to derived items.
+#[derive(Resource, Default)]
+pub struct InheritableComponents {
+    components: Vec<InheritableComponent>,
+    /// Flattens items as soon as they get a [`Template`] or [`Templates`].
+    pub auto_flatten: bool,
+}
+
+/// Type-erased operations of a single inheritable component.
+struct InheritableComponent {
+    id: TypeId,
+    inherit: fn(&mut World, Entity),
+}
+
+impl InheritableComponents {
+    pub fn register<C: Component + Clone>(&mut self) {
+        let id = TypeId::of::<C>();
+        if self.components.iter().all(|c| c.id != id) {
+            self.components.push(InheritableComponent {
+                id,
+                inherit: inherit::<C>,
+            });
+        }
+    }
+
+    /// Copies all registered components the item doesn't have from its nearest template.
+    pub fn flatten(&self, world: &mut World, item: Entity) {
+        for component in &self.components {
+            (component.inherit)(world, item);
+        }
+    }
+}
+
+/// Components which were copied from a template rather than overridden.
+#[derive(Component, Default)]
+pub struct Inherited(pub(crate) HashSet<TypeId>);
+
+impl Inherited {
+    pub fn contains<C: Component>(&self) -> bool {
+        self.0.contains(&TypeId::of::<C>())
+    }
+}
+
+/// Components combining their value with the values of all templates,
+/// e.g. lists of tags or stat modifiers, see [`ItemData::merged_get`](crate::params::ItemData::merged_get).
+///
+/// Merged components shouldn't be registered as inheritable,
+/// copies would be merged with the value they were copied from.
+pub trait MergeInherit: Component + Clone {
+    /// Combines this item's value with the merged value of its template.
+    fn merge(&mut self, inherited: &Self);
+}
+
+/// Registration of inheritable components on [`App`].
+pub trait InheritAppExt {
+    /// Registers the component and starts propagating its changes.
+    fn register_inheritable<C: Component + Clone>(&mut self) -> &mut Self;
+}
+
+impl InheritAppExt for App {
+    fn register_inheritable<C: Component + Clone>(&mut self) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_insert_with(InheritableComponents::default)
+            .register::<C>();
+        self.add_observer(component_overridden::<C>);
+        self.add_systems(
+            PostUpdate,
+            template_changed::<C>.before(InventorySystem::ApplyTransactions),
+        );
+        self
+    }
+}
+
+/// Copies inheritable components of templates onto all items based on them,
+/// so lookups don't have to traverse the [`Template`] chain.
+///
+/// Components already present on an item are treated as overrides and kept.
+/// Later changes of templates are propagated to the copies.
+pub struct FlattenTemplates;
+
+impl Command for FlattenTemplates {
+    fn apply(self, world: &mut World) {
+        let items: Vec<_> = world
+            .query_filtered::<Entity, (With<Item>, Or<(With<Template>, With<Templates>)>)>()
+            .iter(world)
+            .collect();
+        world.resource_scope(|world, registry: Mut<InheritableComponents>| {
+            for item in items {
+                registry.flatten(world, item);
+            }
+        });
+    }
+}
+
+fn inherit<C: Component + Clone>(world: &mut World, item: Entity) {
+    if world.get::<C>(item).is_some() {
+        return;
+    }
+    let Ok(order) = resolution_order(world, item) else {
+        return;
+    };
+    // Inherited copies could be outdated, so only overrides are copied.
+    let Some(component) = order.into_iter().skip(1).find_map(|template| {
+        let inherited = world
+            .get::<Inherited>(template)
+            .is_some_and(Inherited::contains::<C>);
+        world.get::<C>(template).filter(|_| !inherited).cloned()
+    }) else {
+        return;
+    };
+    insert_inherited(world, item, component);
+}
+
+/// Inserts the component and marks it as [`Inherited`].
+fn insert_inherited<C: Component>(world: &mut World, item: Entity, component: C) {
+    let mut item = world.entity_mut(item);
+    item.insert(component);
+    item.entry::<Inherited>()
+        .or_default()
+        .0
+        .insert(TypeId::of::<C>());
+}
+
+/// Inserting a component directly turns it into an override.
+fn component_overridden<C: Component>(
+    trigger: Trigger<OnInsert, C>,
+    mut inherited: Query<&mut Inherited>,
+) {
+    if let Ok(mut inherited) = inherited.get_mut(trigger.entity()) {
+        inherited.0.remove(&TypeId::of::<C>());
+    }
+}
+
+/// Propagates changes and removals of component `C` on templates to copies in derived items.
+fn template_changed<C: Component + Clone>(
+    changed: Query<Entity, Changed<C>>,
+    mut removed: RemovedComponents<C>,
+    items: Query<Has<C>, With<Item>>,
+    inherited: Query<&Inherited>,
+    mut commands: Commands,
+) {
+    // Items which got the component back are handled as changed.
+    let removed: HashSet<_> = removed
+        .read()
+        .filter(|&template| items.get(template).is_ok_and(|has| !has))
+        .collect();
+    for template in changed.iter().chain(removed) {
+        // Inherited copies are updated by propagation from their template.
+        if inherited.get(template).is_ok_and(Inherited::contains::<C>) {
+            continue;
+        }
+        commands.queue(move |world: &mut World| propagate::<C>(world, template));
+        commands.trigger_targets(ItemChanged, template);
+    }
+}
+
+/// Refreshes inherited copies of `C` in items derived from the template.
+fn propagate<C: Component + Clone>(world: &mut World, template: Entity) {
+    let descendants: Vec<_> = world.iter_descendants(template).collect();
+    for item in descendants {
+        let Some(mut inherited) = world.get_mut::<Inherited>(item) else {
+            continue;
+        };
+        if inherited.0.remove(&TypeId::of::<C>()) {
+            world.entity_mut(item).remove::<C>();
+            inherit::<C>(world, item);
+        }
+    }
+}
+
+/// Notifies items derived from the changed item.
+fn forward_item_changed(
+    trigger: Trigger<ItemChanged>,
+    derived: Query<&DerivedItems>,
+    mut commands: Commands,
+) {
+    let item = trigger.entity();
+    let Ok(derived) = derived.get(item) else {
+        return;
+    };
+    for entity in derived.iter().filter(|&e| e != item) {
+        commands.trigger_targets(ItemChanged, entity);
+    }
+}
+
+fn auto_flatten<L: Component>(
+    trigger: Trigger<OnAdd, L>,
+    registry: Res<InheritableComponents>,
+    mut commands: Commands,
+) {
+    if !registry.auto_flatten {
+        return;
+    }
+    let item = trigger.entity();
+    commands.queue(move |world: &mut World| {
+        if world.get::<Item>(item).is_none() {
+            return;
+        }
+        world.resource_scope(|world, registry: Mut<InheritableComponents>| {
+            registry.flatten(world, item);
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HeadlessItemPlugin;
+
+    fn max_stack(app: &App, item: Entity) -> Option<u32> {
+        app.world()
+            .get::<MaxStack>(item)
+            .map(|max_stack| max_stack.0)
+    }
+
+    /// Template with a [`MaxStack`], an item based on it and an item based on that item.
+    fn chain(app: &mut App) -> [Entity; 3] {
+        let world = app.world_mut();
+        let template = world.spawn((Item, MaxStack(8))).id();
+        let item = world.spawn((Item, Template(template))).id();
+        let derived = world.spawn((Item, Template(item))).id();
+        FlattenTemplates.apply(world);
+        [template, item, derived]
+    }
+
+    #[test]
+    fn template_change_propagates() {
+        let mut app = App::new();
+        app.add_plugins(HeadlessItemPlugin);
+        let [template, item, derived] = chain(&mut app);
+        let overriding = app
+            .world_mut()
+            .spawn((Item, Template(template), MaxStack(2)))
+            .id();
+        assert_eq!(max_stack(&app, item), Some(8));
+        assert_eq!(max_stack(&app, derived), Some(8));
+        app.update();
+
+        app.world_mut().get_mut::<MaxStack>(template).unwrap().0 = 16;
+        app.update();
+
+        assert_eq!(max_stack(&app, item), Some(16));
+        assert_eq!(max_stack(&app, derived), Some(16));
+        assert_eq!(max_stack(&app, overriding), Some(2));
+    }
+
+    #[test]
+    fn template_removal_propagates() {
+        let mut app = App::new();
+        app.add_plugins(HeadlessItemPlugin);
+        let [template, item, derived] = chain(&mut app);
+        let base = app.world_mut().spawn((Item, MaxStack(4))).id();
+        app.world_mut().entity_mut(template).insert(Template(base));
+        app.update();
+
+        // The copy falls back to the next template.
+        app.world_mut().entity_mut(template).remove::<MaxStack>();
+        app.update();
+        assert_eq!(max_stack(&app, item), Some(4));
+        assert_eq!(max_stack(&app, derived), Some(4));
+
+        app.world_mut().entity_mut(base).remove::<MaxStack>();
+        app.update();
+        assert_eq!(max_stack(&app, item), None);
+        assert_eq!(max_stack(&app, derived), None);
+    }
+}
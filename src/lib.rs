@@ -1,24 +1,107 @@
 //! Highly configurable crate for items and inventories.
 
 pub mod components;
+pub mod definitions;
+pub mod equipment;
+pub mod events;
+pub mod filter;
+pub mod grid;
+pub mod history;
+pub mod inherit;
+pub mod lifecycle;
+pub mod ops;
+pub mod ownership;
 pub mod params;
+pub mod registry;
+pub mod save;
+pub mod scene;
+pub mod stack;
+pub mod transaction;
 pub mod ui;
 
 use bevy::prelude::*;
 
+/// Items and inventories with UI.
 pub struct ItemPlugin;
 
 impl Plugin for ItemPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins((ui::plugin,));
+        app.add_plugins((HeadlessItemPlugin, ItemDefinitionsPlugin, ui::plugin));
+    }
+}
+
+/// Items and inventories without UI, e.g. for servers.
+/// Item definitions are loaded by the separate [`ItemDefinitionsPlugin`].
+pub struct HeadlessItemPlugin;
+
+impl Plugin for HeadlessItemPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<components::Item>()
+            .register_type::<components::Prototype>()
+            .register_type::<components::Icon>()
+            .register_type::<components::Template>()
+            .register_type::<components::Templates>()
+            .register_type::<components::Count>()
+            .register_type::<components::MaxStack>()
+            .register_type::<components::Inventory>()
+            .register_type::<grid::Shape>()
+            .register_type::<grid::Rotated>()
+            .register_type::<grid::GridInventory>()
+            .register_type::<registry::ItemId>();
+        app.init_resource::<params::MaxTemplateDepth>();
+        app.add_plugins((
+            equipment::plugin,
+            inherit::plugin,
+            lifecycle::plugin,
+            registry::plugin,
+            stack::plugin,
+            transaction::plugin,
+        ));
+    }
+}
+
+/// Loading of item templates from `.items.ron` definition assets, included in [`ItemPlugin`].
+/// Requires the `AssetPlugin`.
+pub struct ItemDefinitionsPlugin;
+
+impl Plugin for ItemDefinitionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(definitions::plugin);
     }
 }
 
 pub mod prelude {
     pub use super::{
-        components::{Count, Icon, Inventory, Item, Template},
-        params::ItemData,
+        components::{
+            Count, DerivedItems, DescendantsExt, Icon, Inventory, Item, MaxStack, Prototype,
+            Template, Templates,
+        },
+        definitions::{DefinedItems, ItemDefinition, ItemDefinitions},
+        equipment::{Equipment, EquipmentData, EquipmentSlot, ItemEquipped, ItemUnequipped},
+        events::{
+            DuplicateItemId, InvalidPrototype, InvalidTemplate, InventoryChanged,
+            InventoryOperationFailed, ItemChanged, ItemDropped,
+        },
+        filter::{InventoryFilter, ItemFilter, SlotFilters},
+        grid::{GridInventory, Rotated, Shape},
+        history::{InventoryHistory, RedoInventory, UndoInventory, UntrackedInventory},
+        inherit::{
+            FlattenTemplates, InheritAppExt, InheritableComponents, Inherited, MergeInherit,
+        },
+        lifecycle::ContentsPolicy,
+        ops::{InventoryCommandsExt, InventoryError, InventoryOps, InventoryWorldExt},
+        ownership::{ContainedIn, SharedItems},
+        params::{
+            InstanceData, ItemData, ItemWorldExt, MaxTemplateDepth, PrototypeData, TemplateError,
+        },
+        registry::{ItemId, ItemRegistry},
+        save::{SaveError, SavedInventory, SavedItem},
+        scene::inventory_scene,
+        stack::StackRule,
+        transaction::{
+            InventoryOperation, InventorySystem, InventoryTransaction, InventoryTransactions,
+        },
         ui::prelude::*,
-        ItemPlugin,
+        HeadlessItemPlugin, ItemDefinitionsPlugin, ItemPlugin,
     };
 }
@@ -0,0 +1,224 @@
+//! Cleanup of slots and contained items when inventories or items are removed or despawned.
+
+use bevy::{
+    ecs::{component::ComponentId, world::DeferredWorld},
+    prelude::*,
+};
+
+use crate::{
+    components::Inventory,
+    events::{InventoryChanged, ItemDropped},
+    ops::InventoryWorldExt,
+    ownership::{self, ContainedIn, SharedItems},
+};
+
+pub fn plugin(app: &mut App) {
+    app.init_resource::<ContentsPolicy>();
+}
+
+/// What happens to items of an inventory once it's removed or despawned.
+/// The resource applies to all inventories, the component overrides it for a single inventory.
+#[derive(Resource, Component, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ContentsPolicy {
+    /// Items are despawned with the inventory.
+    #[default]
+    Despawn,
+    /// Items are kept and reported with [`ItemDropped`], e.g. to spawn them into the game world.
+    Drop,
+    /// Items are moved into free slots of the fallback inventory, items which don't fit are dropped.
+    MoveTo(Entity),
+}
+
+impl ContentsPolicy {
+    /// Policy of the inventory, falling back to the resource.
+    pub fn of(world: &World, inventory: Entity) -> Self {
+        world
+            .get::<ContentsPolicy>(inventory)
+            .or(world.get_resource::<ContentsPolicy>())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn apply(self, world: &mut World, inventory: Entity, item: Entity) {
+        match self {
+            Self::Despawn => {
+                world.entity_mut(item).despawn_recursive();
+            }
+            Self::Drop => {
+                world.trigger_targets(ItemDropped { inventory }, item);
+            }
+            Self::MoveTo(fallback) => {
+                let mut ops = world.inventory_ops();
+                let moved = ops
+                    .first_free_slot_for(fallback, item)
+                    .ok()
+                    .flatten()
+                    .is_some_and(|index| ops.insert(fallback, index, item).is_ok());
+                if !moved {
+                    world.trigger_targets(ItemDropped { inventory }, item);
+                }
+            }
+        }
+    }
+}
+
+/// Applies the [`ContentsPolicy`] to items of the removed inventory.
+pub(crate) fn inventory_removed(mut world: DeferredWorld, entity: Entity, _: ComponentId) {
+    let items: Vec<_> = world
+        .get::<Inventory>(entity)
+        .unwrap()
+        .0
+        .iter()
+        .flatten()
+        .copied()
+        .collect();
+    let policy = ContentsPolicy::of(&world, entity);
+    world
+        .commands()
+        .queue(move |world: &mut World| release_items(world, entity, items, policy));
+}
+
+/// Applies the policy to items taken out of the inventory.
+pub(crate) fn release_items(
+    world: &mut World,
+    inventory: Entity,
+    items: Vec<Entity>,
+    policy: ContentsPolicy,
+) {
+    for item in items {
+        // Items could've been despawned or claimed by another slot in the meantime.
+        if world.get_entity(item).is_err() || ownership::contained_in(world, item).is_some() {
+            continue;
+        }
+        policy.apply(world, inventory, item);
+    }
+}
+
+/// Clears slots holding the removed item.
+pub(crate) fn item_removed(mut world: DeferredWorld, entity: Entity, _: ComponentId) {
+    let contained = world.get::<ContainedIn>(entity).copied();
+    let shared = world.contains_resource::<SharedItems>();
+    if contained.is_none() && !shared {
+        return;
+    }
+    world
+        .commands()
+        .queue(move |world: &mut World| clear_slots(world, entity, contained));
+}
+
+/// Empties slots holding the item, found by its [`ContainedIn`].
+/// Shared items aren't tracked by [`ContainedIn`], so all inventories are searched for them.
+pub(crate) fn clear_slots(world: &mut World, item: Entity, contained: Option<ContainedIn>) {
+    let slots: Vec<_> = match contained {
+        Some(contained) => vec![(contained.inventory, contained.index)],
+        None if world.contains_resource::<SharedItems>() => world
+            .query::<(Entity, &Inventory)>()
+            .iter(world)
+            .flat_map(|(inventory, slots)| {
+                slots
+                    .0
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, &slot)| slot == Some(item))
+                    .map(move |(index, _)| (inventory, index))
+            })
+            .collect(),
+        None => return,
+    };
+    for (inventory, index) in slots {
+        let Some(slot) = world
+            .get_mut::<Inventory>(inventory)
+            .and_then(|inventory| inventory.into_inner().0.get_mut(index))
+            .filter(|slot| **slot == Some(item))
+        else {
+            continue;
+        };
+        *slot = None;
+        ownership::slot_changed(world, inventory, index, Some(item), None);
+        world.trigger_targets(InventoryChanged, inventory);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::Item;
+
+    /// Inventory holding a single item, the policy is set on the inventory if given.
+    fn inventory(world: &mut World, policy: Option<ContentsPolicy>) -> (Entity, Entity) {
+        let item = world.spawn(Item).id();
+        let mut inventory = world.spawn(Inventory(vec![Some(item)]));
+        if let Some(policy) = policy {
+            inventory.insert(policy);
+        }
+        let inventory = inventory.id();
+        world.flush();
+        (inventory, item)
+    }
+
+    /// Items reported with [`ItemDropped`].
+    #[derive(Resource, Default)]
+    struct Dropped(Vec<(Entity, Entity)>);
+
+    fn record_dropped(world: &mut World) {
+        world.init_resource::<Dropped>();
+        world.add_observer(
+            |trigger: Trigger<ItemDropped>, mut dropped: ResMut<Dropped>| {
+                dropped.0.push((trigger.inventory, trigger.entity()));
+            },
+        );
+    }
+
+    #[test]
+    fn despawn_policy() {
+        let mut world = World::new();
+        let (inventory, item) = inventory(&mut world, None);
+
+        world.entity_mut(inventory).despawn();
+        world.flush();
+
+        assert!(world.get_entity(item).is_err());
+    }
+
+    #[test]
+    fn drop_policy() {
+        let mut world = World::new();
+        record_dropped(&mut world);
+        world.insert_resource(ContentsPolicy::Drop);
+        let (inventory, item) = inventory(&mut world, None);
+
+        world.entity_mut(inventory).remove::<Inventory>();
+        world.flush();
+
+        assert!(world.get_entity(item).is_ok());
+        assert!(world.get::<ContainedIn>(item).is_none());
+        assert_eq!(world.resource::<Dropped>().0, [(inventory, item)]);
+    }
+
+    #[test]
+    fn move_to_policy() {
+        let mut world = World::new();
+        record_dropped(&mut world);
+        let fallback = world.spawn(Inventory::with_size(1)).id();
+        let policy = ContentsPolicy::MoveTo(fallback);
+        let (first, moved) = inventory(&mut world, Some(policy));
+        let (second, dropped) = inventory(&mut world, Some(policy));
+
+        world.entity_mut(first).despawn();
+        world.flush();
+        world.entity_mut(second).despawn();
+        world.flush();
+
+        assert_eq!(world.get::<Inventory>(fallback).unwrap().0, [Some(moved)]);
+        assert_eq!(
+            world.get::<ContainedIn>(moved),
+            Some(&ContainedIn {
+                inventory: fallback,
+                index: 0
+            })
+        );
+        // The fallback is full, so the second item is dropped.
+        assert!(world.get_entity(dropped).is_ok());
+        assert_eq!(world.resource::<Dropped>().0, [(second, dropped)]);
+    }
+}
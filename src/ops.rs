@@ -0,0 +1,1043 @@
+//! Inventory operations independent of the UI.
+
+use std::{borrow::Cow, fmt};
+
+use bevy::prelude::*;
+
+use crate::{
+    components::{Count, Inventory, Item},
+    events::{InventoryChanged, InventoryOperationFailed, ItemChanged},
+    filter::{InventoryFilter, SlotFilters},
+    grid::{self, Rotated},
+    history::{InventoryHistory, InventoryRecord, UntrackedInventory},
+    ownership,
+    stack::{can_stack, merge_stacks, split_stack},
+};
+
+/// Reason why an inventory operation failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InventoryError {
+    /// Entity doesn't exist or has no [`Inventory`].
+    MissingInventory(Entity),
+    /// Slot index is outside of the inventory.
+    IndexOutOfBounds { inventory: Entity, index: usize },
+    /// Slot already contains an item.
+    SlotOccupied { inventory: Entity, index: usize },
+    /// Slot doesn't contain an item.
+    EmptySlot { inventory: Entity, index: usize },
+    /// Entity doesn't exist or has no [`Item`].
+    NotAnItem(Entity),
+    /// Item is already in another slot.
+    AlreadyContained {
+        item: Entity,
+        inventory: Entity,
+        index: usize,
+    },
+    /// Templates can't be put into slots.
+    Template(Entity),
+    /// Items can't be merged into a single stack.
+    NotStackable { from: Entity, to: Entity },
+    /// Item overlaps another item or the edge of a grid inventory.
+    DoesNotFit { inventory: Entity, index: usize },
+    /// Slot no longer holds the item recorded in the [`InventoryHistory`].
+    SlotChanged { inventory: Entity, index: usize },
+    /// Item was rejected by a game rule.
+    RuleRejected {
+        item: Entity,
+        reason: Cow<'static, str>,
+    },
+}
+
+impl fmt::Display for InventoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingInventory(inventory) => write!(f, "{inventory} is not an inventory"),
+            Self::IndexOutOfBounds { inventory, index } => {
+                write!(f, "slot {index} is out of bounds of inventory {inventory}")
+            }
+            Self::SlotOccupied { inventory, index } => {
+                write!(f, "slot {index} of inventory {inventory} is occupied")
+            }
+            Self::EmptySlot { inventory, index } => {
+                write!(f, "slot {index} of inventory {inventory} is empty")
+            }
+            Self::NotAnItem(item) => write!(f, "{item} is not an item"),
+            Self::AlreadyContained {
+                item,
+                inventory,
+                index,
+            } => write!(
+                f,
+                "{item} is already in slot {index} of inventory {inventory}"
+            ),
+            Self::Template(item) => write!(f, "{item} is a template"),
+            Self::SlotChanged { inventory, index } => {
+                write!(
+                    f,
+                    "slot {index} of inventory {inventory} changed since it was recorded"
+                )
+            }
+            Self::DoesNotFit { inventory, index } => {
+                write!(
+                    f,
+                    "item in slot {index} of inventory {inventory} doesn't fit"
+                )
+            }
+            Self::NotStackable { from, to } => write!(f, "{from} can't be stacked onto {to}"),
+            Self::RuleRejected { item, reason } => write!(f, "{item} was rejected: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for InventoryError {}
+
+/// Operations on inventories.
+/// Every operation which changes an inventory emits [`InventoryChanged`] for it.
+pub struct InventoryOps<'w> {
+    world: &'w mut World,
+    journal: Option<Journal>,
+}
+
+impl<'w> InventoryOps<'w> {
+    pub fn new(world: &'w mut World) -> Self {
+        Self {
+            world,
+            journal: None,
+        }
+    }
+
+    /// Applies operations atomically, if any of them fails all changes are rolled back.
+    /// Events and despawns are deferred until all operations succeed.
+    /// Nested transactions become part of the outer transaction,
+    /// but their changes are rolled back on failure even if the outer transaction handles the error.
+    pub fn transaction<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, InventoryError>,
+    ) -> Result<T, InventoryError> {
+        self.run_transaction(Journal::default(), f)
+    }
+
+    /// Like [`Self::transaction`], but the changes aren't recorded into the [`InventoryHistory`].
+    pub(crate) fn untracked_transaction<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, InventoryError>,
+    ) -> Result<T, InventoryError> {
+        let journal = Journal {
+            untracked: true,
+            ..Default::default()
+        };
+        self.run_transaction(journal, f)
+    }
+
+    fn run_transaction<T>(
+        &mut self,
+        journal: Journal,
+        f: impl FnOnce(&mut Self) -> Result<T, InventoryError>,
+    ) -> Result<T, InventoryError> {
+        if let Some(journal) = &self.journal {
+            let savepoint = journal.savepoint();
+            let result = f(self);
+            if result.is_err() {
+                let journal = self.journal.as_mut().unwrap();
+                journal.rollback_to(self.world, savepoint);
+            }
+            return result;
+        }
+        self.journal = Some(journal);
+        let result = f(self).and_then(|value| {
+            self.validate()?;
+            Ok(value)
+        });
+        let journal = self.journal.take().unwrap();
+        match result {
+            Ok(_) => journal.commit(self.world),
+            Err(_) => journal.rollback(self.world),
+        }
+        result
+    }
+
+    /// Item in the slot.
+    pub fn get(&self, inventory: Entity, index: usize) -> Result<Option<Entity>, InventoryError> {
+        let slots = &self
+            .world
+            .get::<Inventory>(inventory)
+            .ok_or(InventoryError::MissingInventory(inventory))?
+            .0;
+        slots
+            .get(index)
+            .copied()
+            .ok_or(InventoryError::IndexOutOfBounds { inventory, index })
+    }
+
+    /// Amount of the item, items without [`Count`] are a single unit.
+    pub fn count(&self, item: Entity) -> u32 {
+        self.world.get::<Count>(item).map_or(1, |c| c.0)
+    }
+
+    /// Index of the first empty slot.
+    pub fn first_free_slot(&self, inventory: Entity) -> Result<Option<usize>, InventoryError> {
+        let slots = &self
+            .world
+            .get::<Inventory>(inventory)
+            .ok_or(InventoryError::MissingInventory(inventory))?
+            .0;
+        Ok(slots.iter().position(Option::is_none))
+    }
+
+    /// Index of the first empty slot which accepts the item.
+    /// [`InventoryFilter`] and the grid occupancy are evaluated once for all slots.
+    pub fn first_free_slot_for(
+        &mut self,
+        inventory: Entity,
+        item: Entity,
+    ) -> Result<Option<usize>, InventoryError> {
+        let slots = self
+            .world
+            .get::<Inventory>(inventory)
+            .ok_or(InventoryError::MissingInventory(inventory))?
+            .0
+            .clone();
+        let filters = self
+            .world
+            .get::<InventoryFilter>(inventory)
+            .map(|filter| filter.0.clone())
+            .unwrap_or_default();
+        if !filters
+            .iter()
+            .all(|filter| filter.accepts(self.world, item))
+        {
+            return Ok(None);
+        }
+        let slot_filters = self
+            .world
+            .get::<SlotFilters>(inventory)
+            .map(|filters| filters.0.clone())
+            .unwrap_or_default();
+        let fitting = grid::fitting_slots(self.world, inventory, item);
+        for (index, slot) in slots.into_iter().enumerate() {
+            if slot.is_some() || fitting.as_ref().is_some_and(|fitting| !fitting[index]) {
+                continue;
+            }
+            let slot_filter = slot_filters.get(index).cloned().flatten();
+            if slot_filter.is_none_or(|filter| filter.accepts(self.world, item)) {
+                return Ok(Some(index));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Checks the item against [`InventoryFilter`] and [`SlotFilters`] of the slot.
+    pub fn accepts(
+        &mut self,
+        inventory: Entity,
+        index: usize,
+        item: Entity,
+    ) -> Result<(), InventoryError> {
+        let mut filters = self
+            .world
+            .get::<InventoryFilter>(inventory)
+            .map(|filter| filter.0.clone())
+            .unwrap_or_default();
+        let slot_filter = self
+            .world
+            .get::<SlotFilters>(inventory)
+            .and_then(|filters| filters.0.get(index).cloned().flatten());
+        filters.extend(slot_filter);
+        for filter in filters {
+            if !filter.accepts(self.world, item) {
+                return Err(InventoryError::RuleRejected {
+                    item,
+                    reason: filter.reason,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the items in both slots can be merged into a single stack.
+    pub fn can_merge(
+        &self,
+        inventory_a: Entity,
+        index_a: usize,
+        inventory_b: Entity,
+        index_b: usize,
+    ) -> bool {
+        let a = self.get(inventory_a, index_a).ok().flatten();
+        let b = self.get(inventory_b, index_b).ok().flatten();
+        a.zip(b).is_some_and(|(a, b)| can_stack(self.world, a, b))
+    }
+
+    /// Puts the item into an empty slot.
+    /// Templates and items in other slots are rejected, unless items are [`SharedItems`](ownership::SharedItems).
+    pub fn insert(
+        &mut self,
+        inventory: Entity,
+        index: usize,
+        item: Entity,
+    ) -> Result<(), InventoryError> {
+        self.transaction(|ops| {
+            if ops.world.get::<Item>(item).is_none() {
+                return Err(InventoryError::NotAnItem(item));
+            }
+            if ops.get(inventory, index)?.is_some() {
+                return Err(InventoryError::SlotOccupied { inventory, index });
+            }
+            ops.set(inventory, index, Some(item))?;
+            ops.changed(inventory);
+            Ok(())
+        })
+    }
+
+    /// Takes the item out of the slot.
+    pub fn remove(
+        &mut self,
+        inventory: Entity,
+        index: usize,
+    ) -> Result<Option<Entity>, InventoryError> {
+        self.transaction(|ops| {
+            let item = ops.get(inventory, index)?;
+            if item.is_some() {
+                ops.set(inventory, index, None)?;
+                ops.changed(inventory);
+            }
+            Ok(item)
+        })
+    }
+
+    /// Moves the item from slot `a` into the empty slot `b`.
+    pub fn move_item(
+        &mut self,
+        inventory_a: Entity,
+        index_a: usize,
+        inventory_b: Entity,
+        index_b: usize,
+    ) -> Result<Entity, InventoryError> {
+        self.transaction(|ops| {
+            let item = ops
+                .get(inventory_a, index_a)?
+                .ok_or(InventoryError::EmptySlot {
+                    inventory: inventory_a,
+                    index: index_a,
+                })?;
+            if ops.get(inventory_b, index_b)?.is_some() {
+                return Err(InventoryError::SlotOccupied {
+                    inventory: inventory_b,
+                    index: index_b,
+                });
+            }
+            ops.set(inventory_a, index_a, None)?;
+            ops.set(inventory_b, index_b, Some(item))?;
+            ops.changed_pair(inventory_a, inventory_b);
+            Ok(item)
+        })
+    }
+
+    /// Swaps contents of slots `a` and `b`.
+    pub fn swap(
+        &mut self,
+        inventory_a: Entity,
+        index_a: usize,
+        inventory_b: Entity,
+        index_b: usize,
+    ) -> Result<(), InventoryError> {
+        self.transaction(|ops| {
+            let a = ops.get(inventory_a, index_a)?;
+            let b = ops.get(inventory_b, index_b)?;
+            if inventory_a == inventory_b && index_a == index_b {
+                return Ok(());
+            }
+            // Slot `a` is emptied first, so neither item is in two slots in between.
+            ops.set(inventory_a, index_a, None)?;
+            ops.set(inventory_b, index_b, a)?;
+            ops.set(inventory_a, index_a, b)?;
+            ops.changed_pair(inventory_a, inventory_b);
+            Ok(())
+        })
+    }
+
+    /// Moves up to `amount` from the stack in slot `a` onto the stack in slot `b`.
+    /// The source stack is despawned if it gets emptied.
+    /// Returns the moved amount.
+    pub fn merge(
+        &mut self,
+        inventory_a: Entity,
+        index_a: usize,
+        inventory_b: Entity,
+        index_b: usize,
+        amount: u32,
+    ) -> Result<u32, InventoryError> {
+        self.transaction(|ops| {
+            let from = ops
+                .get(inventory_a, index_a)?
+                .ok_or(InventoryError::EmptySlot {
+                    inventory: inventory_a,
+                    index: index_a,
+                })?;
+            let to = ops
+                .get(inventory_b, index_b)?
+                .ok_or(InventoryError::EmptySlot {
+                    inventory: inventory_b,
+                    index: index_b,
+                })?;
+            if !can_stack(ops.world, from, to) {
+                return Err(InventoryError::NotStackable { from, to });
+            }
+            ops.record_count(from);
+            ops.record_count(to);
+            let moved = merge_stacks(ops.world, from, to, amount);
+            if moved == 0 {
+                return Ok(0);
+            }
+            ops.item_changed(to);
+            if ops.world.get::<Count>(from).is_some_and(|c| c.0 > 0) {
+                ops.item_changed(from);
+            } else {
+                ops.set(inventory_a, index_a, None)?;
+                ops.despawn(from);
+                ops.changed(inventory_a);
+            }
+            Ok(moved)
+        })
+    }
+
+    /// Moves `amount` from the stack in slot `a` into slot `b`.
+    /// If slot `b` is empty a new stack is split off, or the whole item is moved if it can't be split.
+    /// Otherwise the amount is merged onto the stack in slot `b`.
+    /// Returns the item in slot `b`.
+    pub fn split(
+        &mut self,
+        inventory_a: Entity,
+        index_a: usize,
+        inventory_b: Entity,
+        index_b: usize,
+        amount: u32,
+    ) -> Result<Entity, InventoryError> {
+        self.transaction(|ops| {
+            let from = ops
+                .get(inventory_a, index_a)?
+                .ok_or(InventoryError::EmptySlot {
+                    inventory: inventory_a,
+                    index: index_a,
+                })?;
+            if let Some(to) = ops.get(inventory_b, index_b)? {
+                ops.merge(inventory_a, index_a, inventory_b, index_b, amount)?;
+                return Ok(to);
+            }
+            ops.record_count(from);
+            let Some(split) = split_stack(ops.world, from, amount) else {
+                return ops.move_item(inventory_a, index_a, inventory_b, index_b);
+            };
+            if let Some(journal) = &mut ops.journal {
+                journal.changes.push(Change::Spawn(split));
+            }
+            ops.set(inventory_b, index_b, Some(split))?;
+            ops.item_changed(from);
+            ops.changed(inventory_b);
+            Ok(split)
+        })
+    }
+
+    /// Moves all items from inventory `a` into inventory `b`.
+    /// Stacks are merged onto compatible stacks first, the rest is put into free slots which accept them.
+    /// Returns the number of slots emptied in inventory `a`.
+    pub fn transfer_all(
+        &mut self,
+        inventory_a: Entity,
+        inventory_b: Entity,
+    ) -> Result<usize, InventoryError> {
+        self.transaction(|ops| {
+            let size_a = ops.size(inventory_a)?;
+            let size_b = ops.size(inventory_b)?;
+            let mut emptied = 0;
+            for index_a in 0..size_a {
+                if ops.get(inventory_a, index_a)?.is_none() {
+                    continue;
+                }
+                for index_b in 0..size_b {
+                    if ops.can_merge(inventory_a, index_a, inventory_b, index_b) {
+                        ops.merge(inventory_a, index_a, inventory_b, index_b, u32::MAX)?;
+                    }
+                }
+                let Some(item) = ops.get(inventory_a, index_a)? else {
+                    emptied += 1;
+                    continue;
+                };
+                let Some(index_b) = ops.first_free_slot_for(inventory_b, item)? else {
+                    continue;
+                };
+                ops.move_item(inventory_a, index_a, inventory_b, index_b)?;
+                emptied += 1;
+            }
+            Ok(emptied)
+        })
+    }
+
+    /// Toggles [`Rotated`] of the item in the slot.
+    pub fn rotate(&mut self, inventory: Entity, index: usize) -> Result<(), InventoryError> {
+        self.transaction(|ops| {
+            let item = ops
+                .get(inventory, index)?
+                .ok_or(InventoryError::EmptySlot { inventory, index })?;
+            toggle_rotated(ops.world, item);
+            if let Some(journal) = &mut ops.journal {
+                journal.changes.push(Change::Rotate(item));
+            }
+            ops.item_changed(item);
+            ops.changed(inventory);
+            Ok(())
+        })
+    }
+
+    /// Restores the state from before a recorded change, see [`Self::replay`].
+    pub(crate) fn revert(&mut self, change: &Change) -> Result<(), InventoryError> {
+        self.replay(change, true)
+    }
+
+    /// Restores the state from after a recorded change, see [`Self::replay`].
+    pub(crate) fn reapply(&mut self, change: &Change) -> Result<(), InventoryError> {
+        self.replay(change, false)
+    }
+
+    /// Applies one side of a recorded change with all checks of regular operations.
+    /// Fails if the slot was changed outside of the history or the recorded item no longer exists.
+    fn replay(&mut self, change: &Change, undo: bool) -> Result<(), InventoryError> {
+        match *change {
+            Change::Slot {
+                inventory,
+                index,
+                old,
+                new,
+            } => {
+                let (expected, target) = if undo { (new, old) } else { (old, new) };
+                if self.get(inventory, index)? != expected {
+                    return Err(InventoryError::SlotChanged { inventory, index });
+                }
+                if let Some(item) = target.filter(|&item| self.world.get::<Item>(item).is_none()) {
+                    return Err(InventoryError::NotAnItem(item));
+                }
+                self.set(inventory, index, target)?;
+                self.changed(inventory);
+            }
+            Change::Count { item, old, new } => {
+                self.record_count(item);
+                set_count(self.world, item, if undo { old } else { new });
+                self.item_changed(item);
+            }
+            Change::Rotate(item) => {
+                toggle_rotated(self.world, item);
+                if let Some(journal) = &mut self.journal {
+                    journal.changes.push(Change::Rotate(item));
+                }
+                self.item_changed(item);
+            }
+            Change::Spawn(_) | Change::Despawn(_) => {}
+        }
+        Ok(())
+    }
+
+    /// Checks layouts of changed grid inventories.
+    fn validate(&mut self) -> Result<(), InventoryError> {
+        let Some(journal) = &self.journal else {
+            return Ok(());
+        };
+        for inventory in journal.inventories.clone() {
+            grid::validate(self.world, inventory)?;
+        }
+        Ok(())
+    }
+
+    fn size(&self, inventory: Entity) -> Result<usize, InventoryError> {
+        self.world
+            .get::<Inventory>(inventory)
+            .map(|inventory| inventory.0.len())
+            .ok_or(InventoryError::MissingInventory(inventory))
+    }
+
+    /// Overwrites a slot, it has to be validated with [`Self::get`] beforehand.
+    /// New items have to pass the slot's filters and ownership checks.
+    fn set(
+        &mut self,
+        inventory: Entity,
+        index: usize,
+        item: Option<Entity>,
+    ) -> Result<(), InventoryError> {
+        if let Some(item) = item {
+            ownership::check(self.world, inventory, index, item)?;
+            self.accepts(inventory, index, item)?;
+        }
+        let slot = &mut self.world.get_mut::<Inventory>(inventory).unwrap().0[index];
+        let old = std::mem::replace(slot, item);
+        ownership::slot_changed(self.world, inventory, index, old, item);
+        if let Some(journal) = &mut self.journal {
+            journal.changes.push(Change::Slot {
+                inventory,
+                index,
+                old,
+                new: item,
+            });
+        }
+        Ok(())
+    }
+
+    /// Stores the current count of an item, so it can be rolled back.
+    /// The new count is filled in once the transaction is committed.
+    fn record_count(&mut self, item: Entity) {
+        let Some(journal) = &mut self.journal else {
+            return;
+        };
+        if let Some(&Count(old)) = self.world.get::<Count>(item) {
+            journal.changes.push(Change::Count {
+                item,
+                old,
+                new: old,
+            });
+        }
+    }
+
+    fn despawn(&mut self, item: Entity) {
+        match &mut self.journal {
+            Some(journal) => journal.changes.push(Change::Despawn(item)),
+            None => {
+                self.world.entity_mut(item).despawn_recursive();
+            }
+        }
+    }
+
+    fn changed(&mut self, inventory: Entity) {
+        match &mut self.journal {
+            Some(journal) => journal.inventories.push(inventory),
+            None => {
+                self.world.trigger_targets(InventoryChanged, inventory);
+            }
+        }
+    }
+
+    pub(crate) fn item_changed(&mut self, item: Entity) {
+        match &mut self.journal {
+            Some(journal) => journal.items.push(item),
+            None => {
+                self.world.trigger_targets(ItemChanged, item);
+            }
+        }
+    }
+
+    fn changed_pair(&mut self, inventory_a: Entity, inventory_b: Entity) {
+        self.changed(inventory_a);
+        if inventory_a != inventory_b {
+            self.changed(inventory_b);
+        }
+    }
+}
+
+/// Changes made during a transaction.
+#[derive(Default)]
+struct Journal {
+    changes: Vec<Change>,
+    inventories: Vec<Entity>,
+    items: Vec<Entity>,
+    /// Changes replaying the history aren't recorded again.
+    untracked: bool,
+}
+
+/// Lengths of the [`Journal`] before a nested transaction.
+#[derive(Default, Clone, Copy)]
+struct Savepoint {
+    changes: usize,
+    inventories: usize,
+    items: usize,
+}
+
+/// Single reversible change made by [`InventoryOps`].
+#[derive(Clone)]
+pub(crate) enum Change {
+    Slot {
+        inventory: Entity,
+        index: usize,
+        old: Option<Entity>,
+        new: Option<Entity>,
+    },
+    Count {
+        item: Entity,
+        old: u32,
+        new: u32,
+    },
+    Rotate(Entity),
+    Spawn(Entity),
+    Despawn(Entity),
+}
+
+impl Change {
+    /// Restores the state from before this change, used to roll back failed transactions.
+    fn revert(&self, world: &mut World) {
+        match *self {
+            Change::Slot {
+                inventory,
+                index,
+                old,
+                ..
+            } => set_slot(world, inventory, index, old),
+            Change::Count { item, old, .. } => set_count(world, item, old),
+            Change::Rotate(item) => toggle_rotated(world, item),
+            Change::Spawn(_) | Change::Despawn(_) => {}
+        }
+    }
+}
+
+fn set_slot(world: &mut World, inventory: Entity, index: usize, item: Option<Entity>) {
+    let Some(slot) = world
+        .get_mut::<Inventory>(inventory)
+        .and_then(|inventory| inventory.into_inner().0.get_mut(index))
+    else {
+        return;
+    };
+    let old = std::mem::replace(slot, item);
+    ownership::slot_changed(world, inventory, index, old, item);
+}
+
+fn toggle_rotated(world: &mut World, item: Entity) {
+    let Ok(mut item) = world.get_entity_mut(item) else {
+        return;
+    };
+    if item.contains::<Rotated>() {
+        item.remove::<Rotated>();
+    } else {
+        item.insert(Rotated);
+    }
+}
+
+fn set_count(world: &mut World, item: Entity, count: u32) {
+    if let Some(mut current) = world.get_mut::<Count>(item) {
+        current.0 = count;
+    }
+}
+
+impl Journal {
+    /// Records the changes into [`InventoryHistory`] if present, applies deferred despawns and emits events.
+    fn commit(mut self, world: &mut World) {
+        for change in &mut self.changes {
+            if let Change::Count { item, new, .. } = change {
+                if let Some(count) = world.get::<Count>(*item) {
+                    *new = count.0;
+                }
+            }
+        }
+        self.items.sort_unstable();
+        self.items.dedup();
+        self.inventories.sort_unstable();
+        self.inventories.dedup();
+        let orphans = if !self.untracked && world.contains_resource::<InventoryHistory>() {
+            let (record, open) = self.record(world);
+            world
+                .resource_mut::<InventoryHistory>()
+                .record(record, open)
+        } else {
+            self.changes
+                .iter()
+                .filter_map(|change| match *change {
+                    Change::Despawn(item) => Some(item),
+                    _ => None,
+                })
+                .collect()
+        };
+        for orphan in orphans {
+            if let Ok(orphan) = world.get_entity_mut(orphan) {
+                orphan.despawn_recursive();
+            }
+        }
+        for item in self.items {
+            if world.get_entity(item).is_ok() {
+                world.trigger_targets(ItemChanged, item);
+            }
+        }
+        for inventory in self.inventories {
+            world.trigger_targets(InventoryChanged, inventory);
+        }
+    }
+
+    /// Record of the changes without slots of [`UntrackedInventory`]s.
+    /// Also returns whether any untracked inventory still holds items, so the next record is merged into this one.
+    fn record(&self, world: &mut World) -> (InventoryRecord, bool) {
+        let mut untracked =
+            world.query_filtered::<(Entity, &Inventory), With<UntrackedInventory>>();
+        let mut open = false;
+        let mut inventories = Vec::new();
+        for (inventory, slots) in untracked.iter(world) {
+            open |= slots.0.iter().any(Option::is_some);
+            inventories.push(inventory);
+        }
+        let changes: Vec<_> = self
+            .changes
+            .iter()
+            .filter(|change| {
+                !matches!(change, Change::Slot { inventory, .. } if inventories.contains(inventory))
+            })
+            .cloned()
+            .collect();
+        let record = InventoryRecord {
+            changes,
+            inventories: self
+                .inventories
+                .iter()
+                .copied()
+                .filter(|inventory| !inventories.contains(inventory))
+                .collect(),
+            items: self.items.clone(),
+        };
+        (record, open)
+    }
+
+    fn savepoint(&self) -> Savepoint {
+        Savepoint {
+            changes: self.changes.len(),
+            inventories: self.inventories.len(),
+            items: self.items.len(),
+        }
+    }
+
+    /// Reverts all changes.
+    fn rollback(mut self, world: &mut World) {
+        self.rollback_to(world, Savepoint::default());
+    }
+
+    /// Reverts changes made since the savepoint.
+    fn rollback_to(&mut self, world: &mut World, savepoint: Savepoint) {
+        for change in self.changes.drain(savepoint.changes..).rev() {
+            change.revert(world);
+            if let Change::Spawn(item) = change {
+                world.entity_mut(item).despawn_recursive();
+            }
+        }
+        self.inventories.truncate(savepoint.inventories);
+        self.items.truncate(savepoint.items);
+    }
+}
+
+/// Access to [`InventoryOps`] from the world.
+pub trait InventoryWorldExt {
+    fn inventory_ops(&mut self) -> InventoryOps<'_>;
+}
+
+impl InventoryWorldExt for World {
+    fn inventory_ops(&mut self) -> InventoryOps<'_> {
+        InventoryOps::new(self)
+    }
+}
+
+/// Access to [`InventoryOps`] from commands.
+pub trait InventoryCommandsExt {
+    /// Queues a command running inventory operations as a single transaction.
+    /// Errors are reported with [`InventoryOperationFailed`].
+    fn inventory_ops(
+        &mut self,
+        f: impl FnOnce(&mut InventoryOps) -> Result<(), InventoryError> + Send + 'static,
+    );
+}
+
+impl InventoryCommandsExt for Commands<'_, '_> {
+    fn inventory_ops(
+        &mut self,
+        f: impl FnOnce(&mut InventoryOps) -> Result<(), InventoryError> + Send + 'static,
+    ) {
+        self.queue(move |world: &mut World| {
+            if let Err(error) = world.inventory_ops().transaction(f) {
+                world.trigger(InventoryOperationFailed(error));
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        components::{MaxStack, Template},
+        filter::{InventoryFilter, ItemFilter},
+        ownership::ContainedIn,
+    };
+
+    /// Template with stacks of the given counts in an inventory with an extra free slot.
+    fn stacks(world: &mut World, counts: &[u32]) -> (Entity, Vec<Entity>) {
+        let template = world.spawn(Item).id();
+        let items: Vec<_> = counts
+            .iter()
+            .map(|&count| world.spawn((Item, Template(template), Count(count))).id())
+            .collect();
+        let mut slots: Vec<_> = items.iter().copied().map(Some).collect();
+        slots.push(None);
+        let inventory = world.spawn(Inventory(slots)).id();
+        world.flush();
+        (inventory, items)
+    }
+
+    fn count(world: &World, item: Entity) -> Option<u32> {
+        world.get::<Count>(item).map(|count| count.0)
+    }
+
+    #[test]
+    fn failed_transaction_rolls_back() {
+        let mut world = World::new();
+        let (inventory, items) = stacks(&mut world, &[5, 3]);
+        let other = world.spawn(Inventory::with_size(1)).id();
+        world.flush();
+
+        let result = world.inventory_ops().transaction(|ops| {
+            ops.merge(inventory, 0, inventory, 1, 5)?;
+            ops.move_item(inventory, 1, other, 0)?;
+            ops.insert(other, 1, items[0])
+        });
+
+        assert_eq!(
+            result,
+            Err(InventoryError::IndexOutOfBounds {
+                inventory: other,
+                index: 1
+            })
+        );
+        assert_eq!(
+            world.get::<Inventory>(inventory).unwrap().0,
+            [Some(items[0]), Some(items[1]), None]
+        );
+        assert_eq!(world.get::<Inventory>(other).unwrap().0, [None]);
+        assert_eq!(count(&world, items[0]), Some(5));
+        assert_eq!(count(&world, items[1]), Some(3));
+        for (index, &item) in items.iter().enumerate() {
+            assert_eq!(
+                world.get::<ContainedIn>(item),
+                Some(&ContainedIn { inventory, index })
+            );
+        }
+    }
+
+    #[test]
+    fn failed_nested_operation_rolls_back() {
+        let mut world = World::new();
+        let (inventory, items) = stacks(&mut world, &[1]);
+        let rejecting = InventoryFilter(vec![ItemFilter::new("rejects all", |_, _| false)]);
+        let other = world.spawn((Inventory::with_size(1), rejecting)).id();
+        world.flush();
+
+        let result = world.inventory_ops().transaction(|ops| {
+            let moved = ops.move_item(inventory, 0, other, 0);
+            assert!(matches!(moved, Err(InventoryError::RuleRejected { .. })));
+            Ok(())
+        });
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            world.get::<Inventory>(inventory).unwrap().0,
+            [Some(items[0]), None]
+        );
+        assert_eq!(world.get::<Inventory>(other).unwrap().0, [None]);
+        assert_eq!(
+            world.get::<ContainedIn>(items[0]),
+            Some(&ContainedIn {
+                inventory,
+                index: 0
+            })
+        );
+    }
+
+    #[test]
+    fn failed_nested_split_despawns_stack() {
+        let mut world = World::new();
+        let (inventory, items) = stacks(&mut world, &[4]);
+        let rejecting = InventoryFilter(vec![ItemFilter::new("rejects all", |_, _| false)]);
+        let other = world.spawn((Inventory::with_size(1), rejecting)).id();
+        world.flush();
+        let entities = world.entities().len();
+
+        let result = world.inventory_ops().transaction(|ops| {
+            assert!(ops.split(inventory, 0, other, 0, 2).is_err());
+            Ok(())
+        });
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(world.entities().len(), entities);
+        assert_eq!(count(&world, items[0]), Some(4));
+        assert_eq!(world.get::<Inventory>(other).unwrap().0, [None]);
+    }
+    #[test]
+    fn merge_leaves_remainder() {
+        let mut world = World::new();
+        let (inventory, items) = stacks(&mut world, &[8, 5]);
+        let template = world.get::<Template>(items[0]).unwrap().0;
+        world.entity_mut(template).insert(MaxStack(10));
+
+        let moved = world.inventory_ops().merge(inventory, 0, inventory, 1, 8);
+
+        assert_eq!(moved, Ok(5));
+        assert_eq!(count(&world, items[0]), Some(3));
+        assert_eq!(count(&world, items[1]), Some(10));
+        assert_eq!(
+            world.get::<Inventory>(inventory).unwrap().0,
+            [Some(items[0]), Some(items[1]), None]
+        );
+    }
+
+    #[test]
+    fn merge_despawns_emptied_stack() {
+        let mut world = World::new();
+        let (inventory, items) = stacks(&mut world, &[4, 5]);
+
+        let moved = world.inventory_ops().merge(inventory, 0, inventory, 1, 10);
+
+        assert_eq!(moved, Ok(4));
+        assert_eq!(count(&world, items[1]), Some(9));
+        assert!(world.get_entity(items[0]).is_err());
+        assert_eq!(
+            world.get::<Inventory>(inventory).unwrap().0,
+            [None, Some(items[1]), None]
+        );
+    }
+
+    #[test]
+    fn split_amounts() {
+        let mut world = World::new();
+        let (inventory, items) = stacks(&mut world, &[8]);
+        let template = world.get::<Template>(items[0]).unwrap().0;
+
+        let split = world.inventory_ops().split(inventory, 0, inventory, 1, 3);
+
+        let split = split.unwrap();
+        assert_ne!(split, items[0]);
+        assert_eq!(count(&world, items[0]), Some(5));
+        assert_eq!(count(&world, split), Some(3));
+        assert_eq!(world.get::<Template>(split).map(|t| t.0), Some(template));
+        assert_eq!(
+            world.get::<ContainedIn>(split),
+            Some(&ContainedIn {
+                inventory,
+                index: 1
+            })
+        );
+
+        // Splitting off the whole stack moves it instead.
+        world.inventory_ops().remove(inventory, 1).unwrap();
+        let moved = world.inventory_ops().split(inventory, 0, inventory, 1, 5);
+        assert_eq!(moved, Ok(items[0]));
+        assert_eq!(count(&world, items[0]), Some(5));
+        assert_eq!(
+            world.get::<Inventory>(inventory).unwrap().0,
+            [None, Some(items[0])]
+        );
+    }
+
+    #[test]
+    fn split_copies_components() {
+        let mut world = World::new();
+        let registry = AppTypeRegistry::default();
+        registry.write().register::<Rotated>();
+        world.insert_resource(registry);
+        let item = world.spawn((Item, Count(10), Rotated)).id();
+        let inventory = world.spawn(Inventory(vec![Some(item), None])).id();
+        world.flush();
+
+        let split = world.inventory_ops().split(inventory, 0, inventory, 1, 1);
+
+        let split = split.unwrap();
+        assert_ne!(split, item);
+        assert_eq!(count(&world, item), Some(9));
+        assert_eq!(count(&world, split), Some(1));
+        assert!(world.get::<Rotated>(split).is_some());
+    }
+}
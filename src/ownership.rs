@@ -0,0 +1,204 @@
+//! Exclusive ownership of items by inventory slots.
+
+use bevy::{
+    ecs::{component::ComponentId, world::DeferredWorld},
+    prelude::*,
+};
+
+use crate::{
+    components::{DerivedItems, Inventory, Prototype},
+    events::{InventoryChanged, InventoryOperationFailed},
+    ops::InventoryError,
+};
+
+/// Points at the inventory and slot index holding this item.
+/// Set when the item is put into a slot by [`InventoryOps`](crate::ops::InventoryOps) or an inserted [`Inventory`],
+/// and cleared once the slot is emptied, the inventory is replaced or removed, or the item becomes a [`Prototype`].
+/// Saves and scenes leave it out, inventories claim their items again when they're loaded.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ContainedIn {
+    pub inventory: Entity,
+    pub index: usize,
+}
+
+/// Opts out of exclusive ownership, allowing the same item in multiple slots,
+/// e.g. for inventories listing items stored elsewhere.
+/// Items which other items are based on are allowed too, [`Prototype`]s are still rejected.
+/// [`ContainedIn`] isn't maintained while this resource exists, so it should be inserted before spawning inventories.
+#[derive(Resource, Default)]
+pub struct SharedItems;
+
+/// Whether the item is a [`Prototype`] or other items are based on it.
+pub fn is_template(world: &World, item: Entity) -> bool {
+    world.get::<Prototype>(item).is_some()
+        || world
+            .get::<DerivedItems>(item)
+            .is_some_and(|derived| derived.iter().next().is_some())
+}
+
+/// Slot currently holding the item, ignoring a stale [`ContainedIn`].
+pub fn contained_in(world: &World, item: Entity) -> Option<ContainedIn> {
+    let contained = *world.get::<ContainedIn>(item)?;
+    let slot = world
+        .get::<Inventory>(contained.inventory)?
+        .0
+        .get(contained.index)
+        .copied()
+        .flatten();
+    (slot == Some(item)).then_some(contained)
+}
+
+/// Rejects [`Prototype`]s, other templates and items contained in another slot.
+/// Only [`Prototype`]s are rejected if items are [`SharedItems`].
+pub(crate) fn check(
+    world: &World,
+    inventory: Entity,
+    index: usize,
+    item: Entity,
+) -> Result<(), InventoryError> {
+    if world.get::<Prototype>(item).is_some() {
+        return Err(InventoryError::Template(item));
+    }
+    if world.contains_resource::<SharedItems>() {
+        return Ok(());
+    }
+    if is_template(world, item) {
+        return Err(InventoryError::Template(item));
+    }
+    match contained_in(world, item) {
+        Some(contained) if contained != (ContainedIn { inventory, index }) => {
+            Err(InventoryError::AlreadyContained {
+                item,
+                inventory: contained.inventory,
+                index: contained.index,
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Moves [`ContainedIn`] from the previous item of the slot to the new one.
+pub(crate) fn slot_changed(
+    world: &mut World,
+    inventory: Entity,
+    index: usize,
+    old: Option<Entity>,
+    new: Option<Entity>,
+) {
+    if world.contains_resource::<SharedItems>() {
+        return;
+    }
+    let slot = ContainedIn { inventory, index };
+    if let Some(old) = old {
+        release(world, old, slot);
+    }
+    if let Some(mut new) = new.and_then(|new| world.get_entity_mut(new).ok()) {
+        new.insert(slot);
+    }
+}
+
+fn release(world: &mut World, item: Entity, slot: ContainedIn) {
+    let Ok(mut item) = world.get_entity_mut(item) else {
+        return;
+    };
+    if item.get::<ContainedIn>() == Some(&slot) {
+        item.remove::<ContainedIn>();
+    }
+}
+
+/// Claims items of the inserted inventory.
+/// Slots holding templates or items contained elsewhere are cleared and reported with [`InventoryOperationFailed`].
+pub(crate) fn inventory_inserted(mut world: DeferredWorld, entity: Entity, _: ComponentId) {
+    world.commands().queue(move |world: &mut World| {
+        let Some(slots) = world.get::<Inventory>(entity).map(|i| i.0.clone()) else {
+            return;
+        };
+        let mut cleared = false;
+        for (index, item) in slots.into_iter().enumerate() {
+            let Some(item) = item else {
+                continue;
+            };
+            if let Err(error) = check(world, entity, index, item) {
+                world.get_mut::<Inventory>(entity).unwrap().0[index] = None;
+                world.trigger(InventoryOperationFailed(error));
+                cleared = true;
+                continue;
+            }
+            slot_changed(world, entity, index, None, Some(item));
+        }
+        if cleared {
+            world.trigger_targets(InventoryChanged, entity);
+        }
+    });
+}
+
+/// Releases items of the removed inventory.
+pub(crate) fn inventory_replaced(mut world: DeferredWorld, entity: Entity, _: ComponentId) {
+    let slots = world.get::<Inventory>(entity).unwrap().0.clone();
+    world.commands().queue(move |world: &mut World| {
+        for (index, item) in slots.into_iter().enumerate() {
+            if let Some(item) = item {
+                release(
+                    world,
+                    item,
+                    ContainedIn {
+                        inventory: entity,
+                        index,
+                    },
+                );
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{components::Item, ops::InventoryWorldExt};
+
+    fn inventories(world: &mut World) -> (Entity, Entity, Entity) {
+        let item = world.spawn(Item).id();
+        let a = world.spawn(Inventory(vec![Some(item)])).id();
+        let b = world.spawn(Inventory::with_size(1)).id();
+        world.flush();
+        (item, a, b)
+    }
+
+    #[test]
+    fn item_in_single_slot() {
+        let mut world = World::new();
+        let (item, a, b) = inventories(&mut world);
+
+        let result = world.inventory_ops().insert(b, 0, item);
+
+        assert_eq!(
+            result,
+            Err(InventoryError::AlreadyContained {
+                item,
+                inventory: a,
+                index: 0
+            })
+        );
+        assert_eq!(world.get::<Inventory>(b).unwrap().0, [None]);
+        assert_eq!(
+            world.get::<ContainedIn>(item),
+            Some(&ContainedIn {
+                inventory: a,
+                index: 0
+            })
+        );
+    }
+
+    #[test]
+    fn shared_items() {
+        let mut world = World::new();
+        world.init_resource::<SharedItems>();
+        let (item, a, b) = inventories(&mut world);
+
+        world.inventory_ops().insert(b, 0, item).unwrap();
+
+        assert_eq!(world.get::<Inventory>(a).unwrap().0, [Some(item)]);
+        assert_eq!(world.get::<Inventory>(b).unwrap().0, [Some(item)]);
+        assert!(world.get::<ContainedIn>(item).is_none());
+    }
+}
@@ -1,40 +1,250 @@
 //! System parameters for accessing data from template-based items.
 
+use std::{any::TypeId, fmt};
+
 use bevy::{
     ecs::{
-        query::{QueryData, QueryEntityError, WorldQuery},
-        system::SystemParam,
+        query::{QueryData, QueryEntityError, QueryFilter, WorldQuery},
+        system::{SystemParam, SystemState},
     },
     prelude::*,
+    utils::HashMap,
+};
+
+use crate::{
+    components::{Item, Prototype, Template, Templates},
+    inherit::{Inherited, MergeInherit},
 };
 
-use crate::components::{Item, Template};
+/// Maximum amount of nested templates followed when looking up item data.
+#[derive(Resource, Clone, Copy)]
+pub struct MaxTemplateDepth(pub usize);
+
+impl Default for MaxTemplateDepth {
+    fn default() -> Self {
+        Self(32)
+    }
+}
+
+/// Reason why templates of an item can't be resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateError {
+    /// Entity in the chain doesn't exist or has no [`Item`].
+    NotAnItem(Entity),
+    /// Templates of the item lead back to one of them.
+    Cycle(Entity),
+    /// Templates of the item are nested deeper than [`MaxTemplateDepth`].
+    TooDeep(Entity),
+    /// Templates of the item have no consistent resolution order.
+    InconsistentOrder(Entity),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAnItem(item) => write!(f, "{item} is not an item"),
+            Self::Cycle(item) => write!(f, "templates of {item} form a cycle"),
+            Self::TooDeep(item) => write!(f, "templates of {item} are nested too deep"),
+            Self::InconsistentOrder(item) => {
+                write!(f, "templates of {item} have no consistent order")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+impl From<QueryEntityError<'_>> for TemplateError {
+    fn from(error: QueryEntityError<'_>) -> Self {
+        match error {
+            QueryEntityError::QueryDoesNotMatch(entity, _)
+            | QueryEntityError::NoSuchEntity(entity)
+            | QueryEntityError::AliasedMutability(entity) => Self::NotAnItem(entity),
+        }
+    }
+}
+
+/// Checks templates of the item for cycles, depth and consistent resolution order.
+pub fn validate_templates(world: &World, item: Entity) -> Result<(), TemplateError> {
+    resolution_order(world, item).map(drop)
+}
+
+/// The item followed by its templates in the order their data is looked up.
+pub fn resolution_order(world: &World, item: Entity) -> Result<Vec<Entity>, TemplateError> {
+    let max_depth = world
+        .get_resource::<MaxTemplateDepth>()
+        .copied()
+        .unwrap_or_default();
+    let parents = |entity| Ok(parents(world.get(entity), world.get(entity)));
+    linearize(item, max_depth, &parents)
+}
+
+/// Direct templates of the item, [`Template`] first followed by [`Templates`].
+pub(crate) fn parents(template: Option<&Template>, templates: Option<&Templates>) -> Vec<Entity> {
+    let mut parents: Vec<_> = template.map(|t| t.0).into_iter().collect();
+    for &template in templates.iter().flat_map(|t| &t.0) {
+        if !parents.contains(&template) {
+            parents.push(template);
+        }
+    }
+    parents
+}
+
+/// Resolution order of the item and its templates using C3 linearization.
+/// Every template appears once, before the templates it's based on.
+/// Orders of shared templates are computed once, so diamonds don't multiply the work.
+fn linearize(
+    item: Entity,
+    max_depth: MaxTemplateDepth,
+    parents: &impl Fn(Entity) -> Result<Vec<Entity>, TemplateError>,
+) -> Result<Vec<Entity>, TemplateError> {
+    /// Linearized templates with the length of their longest template chain.
+    type Linearized = HashMap<Entity, (Vec<Entity>, usize)>;
+
+    fn visit(
+        item: Entity,
+        entity: Entity,
+        depth: usize,
+        max_depth: MaxTemplateDepth,
+        visiting: &mut Vec<Entity>,
+        linearized: &mut Linearized,
+        parents: &impl Fn(Entity) -> Result<Vec<Entity>, TemplateError>,
+    ) -> Result<(Vec<Entity>, usize), TemplateError> {
+        if let Some((order, height)) = linearized.get(&entity) {
+            if depth + height > max_depth.0 {
+                return Err(TemplateError::TooDeep(item));
+            }
+            return Ok((order.clone(), *height));
+        }
+        if visiting.contains(&entity) {
+            return Err(TemplateError::Cycle(item));
+        }
+        if depth > max_depth.0 {
+            return Err(TemplateError::TooDeep(item));
+        }
+        let direct = parents(entity)?;
+        visiting.push(entity);
+        let mut lists = Vec::with_capacity(direct.len() + 1);
+        let mut height = 0;
+        for &parent in &direct {
+            let (order, parent_height) = visit(
+                item,
+                parent,
+                depth + 1,
+                max_depth,
+                visiting,
+                linearized,
+                parents,
+            )?;
+            height = height.max(parent_height + 1);
+            lists.push(order);
+        }
+        visiting.pop();
+        lists.push(direct);
+
+        let mut order = vec![entity];
+        loop {
+            lists.retain(|list| !list.is_empty());
+            if lists.is_empty() {
+                break;
+            }
+            // First head which doesn't have to come after another template.
+            let head = lists
+                .iter()
+                .map(|list| list[0])
+                .find(|head| lists.iter().all(|list| !list[1..].contains(head)))
+                .ok_or(TemplateError::InconsistentOrder(item))?;
+            order.push(head);
+            for list in &mut lists {
+                if list[0] == head {
+                    list.remove(0);
+                }
+            }
+        }
+        linearized.insert(entity, (order.clone(), height));
+        Ok((order, height))
+    }
+    visit(
+        item,
+        item,
+        0,
+        max_depth,
+        &mut Vec::new(),
+        &mut HashMap::new(),
+        parents,
+    )
+    .map(|(order, _)| order)
+}
+
+/// Components linking an item to its templates.
+type Links = (Option<&'static Template>, Option<&'static Templates>);
 
 /// System parameter for accessing data from template-based items.
+/// Filter `F` applies to the accessed items, but not to the templates their data is looked up in.
 #[derive(SystemParam)]
-pub struct ItemData<'w, 's, D: QueryData + 'static> {
-    query: Query<'w, 's, (Option<&'static Template>, Option<D>), With<Item>>,
+pub struct ItemData<'w, 's, D: QueryData + 'static, F: QueryFilter + 'static = ()> {
+    query: Query<'w, 's, (Links, Option<D>), With<Item>>,
+    items: Query<'w, 's, (), (With<Item>, F)>,
+    max_depth: Option<Res<'w, MaxTemplateDepth>>,
 }
 
-impl<'w, 's, D: QueryData> ItemData<'w, 's, D> {
-    fn extend_find(&self, mut entity: Entity) -> Result<Option<Entity>, QueryEntityError> {
-        loop {
-            let (maybe_extends, maybe_data) = self.query.get(entity)?;
-            if maybe_data.is_some() {
-                break Ok(Some(entity));
+/// [`ItemData`] of instance items, excluding [`Prototype`]s.
+pub type InstanceData<'w, 's, D> = ItemData<'w, 's, D, Without<Prototype>>;
+
+/// [`ItemData`] of [`Prototype`]s.
+pub type PrototypeData<'w, 's, D> = ItemData<'w, 's, D, With<Prototype>>;
+
+impl<'w, 's, D: QueryData, F: QueryFilter> ItemData<'w, 's, D, F> {
+    pub(crate) fn extend_find(&self, item: Entity) -> Result<Option<Entity>, TemplateError> {
+        self.items.get(item)?;
+        let max_depth = self.max_depth.as_deref().copied().unwrap_or_default();
+        // Chains of single templates are walked up to the first match without resolving the order.
+        let mut entity = item;
+        for _ in 0..=max_depth.0 {
+            let ((template, templates), data) = self.query.get(entity)?;
+            if data.is_some() {
+                return Ok(Some(entity));
+            }
+            if templates.is_some_and(|templates| !templates.0.is_empty()) {
+                return self.find_in_order(item);
             }
-            let Some(&Template(new_entity)) = maybe_extends else {
-                break Ok(None);
+            let Some(template) = template else {
+                return Ok(None);
             };
-            entity = new_entity;
+            entity = template.0;
         }
+        // Chain is too deep or contains a cycle.
+        Err(self
+            .resolution_order(item)
+            .err()
+            .unwrap_or(TemplateError::TooDeep(item)))
+    }
+
+    fn find_in_order(&self, item: Entity) -> Result<Option<Entity>, TemplateError> {
+        for entity in self.resolution_order(item)? {
+            if self.query.get(entity)?.1.is_some() {
+                return Ok(Some(entity));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The item followed by its templates in the order their data is looked up.
+    pub fn resolution_order(&self, item: Entity) -> Result<Vec<Entity>, TemplateError> {
+        self.items.get(item)?;
+        let max_depth = self.max_depth.as_deref().copied().unwrap_or_default();
+        let parents = |entity| {
+            let ((template, templates), _) = self.query.get(entity)?;
+            Ok(parents(template, templates))
+        };
+        linearize(item, max_depth, &parents)
     }
 
     /// Access this item's components immutably if available or fallaback to template's components.
     pub fn extended_get(
         &self,
         entity: Entity,
-    ) -> Result<Option<<D::ReadOnly as WorldQuery>::Item<'_>>, QueryEntityError> {
+    ) -> Result<Option<<D::ReadOnly as WorldQuery>::Item<'_>>, TemplateError> {
         let Some(entity) = self.extend_find(entity)? else {
             return Ok(None);
         };
@@ -45,12 +255,242 @@ impl<'w, 's, D: QueryData> ItemData<'w, 's, D> {
     pub fn get(
         &self,
         entity: Entity,
-    ) -> Result<Option<<D::ReadOnly as WorldQuery>::Item<'_>>, QueryEntityError> {
+    ) -> Result<Option<<D::ReadOnly as WorldQuery>::Item<'_>>, QueryEntityError<'_>> {
+        self.items.get(entity)?;
         Ok(self.query.get(entity)?.1)
     }
 
     /// Access this item's components mutably.
-    pub fn get_mut(&mut self, entity: Entity) -> Result<Option<D::Item<'_>>, QueryEntityError> {
+    pub fn get_mut(&mut self, entity: Entity) -> Result<Option<D::Item<'_>>, QueryEntityError<'_>> {
+        self.items.get(entity)?;
         Ok(self.query.get_mut(entity)?.1)
     }
 }
+
+impl<C: MergeInherit, F: QueryFilter> ItemData<'_, '_, &'static C, F> {
+    /// Combines the component of this item with the components of all its templates.
+    pub fn merged_get(&self, entity: Entity) -> Result<Option<C>, TemplateError> {
+        let order = self.resolution_order(entity)?;
+        let merged = order
+            .into_iter()
+            .rev()
+            .filter_map(|entity| self.query.get(entity).ok()?.1)
+            .fold(None, |inherited: Option<C>, component| {
+                let mut component = component.clone();
+                if let Some(inherited) = &inherited {
+                    component.merge(inherited);
+                }
+                Some(component)
+            });
+        Ok(merged)
+    }
+}
+
+/// Copy-on-write access to inherited item data.
+pub trait ItemWorldExt {
+    /// Access this item's component mutably, cloning it from the template first if it's inherited.
+    /// The item then overrides the template's component, so changes don't affect other items.
+    fn extended_get_mut_or_override<C: Component + Clone>(
+        &mut self,
+        item: Entity,
+    ) -> Result<Option<Mut<'_, C>>, TemplateError>;
+}
+
+impl ItemWorldExt for World {
+    fn extended_get_mut_or_override<C: Component + Clone>(
+        &mut self,
+        item: Entity,
+    ) -> Result<Option<Mut<'_, C>>, TemplateError> {
+        let mut state = SystemState::<ItemData<&C>>::new(self);
+        let items = state.get(self);
+        let Some(owner) = items.extend_find(item)? else {
+            return Ok(None);
+        };
+        if owner != item {
+            let component = self.get::<C>(owner).unwrap().clone();
+            self.entity_mut(item).insert(component);
+        } else if let Some(mut inherited) = self.get_mut::<Inherited>(item) {
+            inherited.0.remove(&TypeId::of::<C>());
+        }
+        Ok(self.get_mut::<C>(item))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        components::MaxStack,
+        inherit::{FlattenTemplates, InheritableComponents},
+    };
+
+    fn template(world: &mut World, template: Option<Entity>, templates: &[Entity]) -> Entity {
+        let mut item = world.spawn(Item);
+        if let Some(template) = template {
+            item.insert(Template(template));
+        }
+        if !templates.is_empty() {
+            item.insert(Templates(templates.to_vec()));
+        }
+        item.id()
+    }
+
+    /// Own tags followed by the tags of templates.
+    #[derive(Component, Clone)]
+    struct Tags(Vec<&'static str>);
+
+    impl MergeInherit for Tags {
+        fn merge(&mut self, inherited: &Self) {
+            for tag in &inherited.0 {
+                if !self.0.contains(tag) {
+                    self.0.push(tag);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn diamond() {
+        let mut world = World::new();
+        let base = template(&mut world, None, &[]);
+        let left = template(&mut world, Some(base), &[]);
+        let right = template(&mut world, Some(base), &[]);
+        let item = template(&mut world, Some(left), &[right]);
+
+        assert_eq!(
+            resolution_order(&world, item),
+            Ok(vec![item, left, right, base])
+        );
+    }
+
+    #[test]
+    fn nested_diamonds() {
+        let mut world = World::new();
+        world.insert_resource(MaxTemplateDepth(128));
+        // Without memoization every diamond doubles the work.
+        let mut item = template(&mut world, None, &[]);
+        for _ in 0..40 {
+            let left = template(&mut world, Some(item), &[]);
+            let right = template(&mut world, Some(item), &[]);
+            item = template(&mut world, Some(left), &[right]);
+        }
+
+        assert_eq!(
+            resolution_order(&world, item).map(|order| order.len()),
+            Ok(121)
+        );
+
+        world.insert_resource(MaxTemplateDepth(79));
+        assert_eq!(
+            resolution_order(&world, item),
+            Err(TemplateError::TooDeep(item))
+        );
+    }
+
+    #[test]
+    fn inconsistent_order() {
+        let mut world = World::new();
+        let a = template(&mut world, None, &[]);
+        let b = template(&mut world, None, &[]);
+        let ab = template(&mut world, None, &[a, b]);
+        let ba = template(&mut world, None, &[b, a]);
+        let item = template(&mut world, None, &[ab, ba]);
+
+        assert_eq!(
+            resolution_order(&world, item),
+            Err(TemplateError::InconsistentOrder(item))
+        );
+        assert_eq!(resolution_order(&world, ab), Ok(vec![ab, a, b]));
+    }
+
+    #[test]
+    fn cycle() {
+        let mut world = World::new();
+        let a = template(&mut world, None, &[]);
+        let b = template(&mut world, Some(a), &[]);
+        world.entity_mut(a).insert(Template(b));
+
+        assert_eq!(resolution_order(&world, b), Err(TemplateError::Cycle(b)));
+    }
+
+    #[test]
+    fn too_deep() {
+        let mut world = World::new();
+        world.insert_resource(MaxTemplateDepth(2));
+        let base = template(&mut world, None, &[]);
+        let middle = template(&mut world, Some(base), &[]);
+        let item = template(&mut world, Some(middle), &[]);
+
+        assert_eq!(resolution_order(&world, item), Ok(vec![item, middle, base]));
+
+        let deeper = template(&mut world, Some(item), &[]);
+        assert_eq!(
+            resolution_order(&world, deeper),
+            Err(TemplateError::TooDeep(deeper))
+        );
+    }
+
+    #[test]
+    fn override_on_first_mutation() {
+        let mut world = World::new();
+        let base = world.spawn((Item, MaxStack(8))).id();
+        let item = world.spawn((Item, Template(base))).id();
+        let other = world.spawn((Item, Template(base))).id();
+
+        world
+            .extended_get_mut_or_override::<MaxStack>(item)
+            .unwrap()
+            .unwrap()
+            .0 = 3;
+
+        assert_eq!(world.get::<MaxStack>(item).map(|m| m.0), Some(3));
+        assert_eq!(world.get::<MaxStack>(base).map(|m| m.0), Some(8));
+        assert!(world.get::<MaxStack>(other).is_none());
+        let mut state = SystemState::<ItemData<&MaxStack>>::new(&mut world);
+        let max_stacks = state.get(&world);
+        assert_eq!(max_stacks.extended_get(other).unwrap().unwrap().0, 8);
+    }
+
+    #[test]
+    fn override_inherited_copy() {
+        let mut world = World::new();
+        let mut inheritable = InheritableComponents::default();
+        inheritable.register::<MaxStack>();
+        world.insert_resource(inheritable);
+        let base = world.spawn((Item, MaxStack(8))).id();
+        let item = world.spawn((Item, Template(base))).id();
+        FlattenTemplates.apply(&mut world);
+        assert!(world.get::<Inherited>(item).unwrap().contains::<MaxStack>());
+
+        world
+            .extended_get_mut_or_override::<MaxStack>(item)
+            .unwrap()
+            .unwrap()
+            .0 = 3;
+
+        assert!(!world.get::<Inherited>(item).unwrap().contains::<MaxStack>());
+        assert_eq!(world.get::<MaxStack>(item).map(|m| m.0), Some(3));
+        assert_eq!(world.get::<MaxStack>(base).map(|m| m.0), Some(8));
+    }
+
+    #[test]
+    fn merge_order() {
+        let mut world = World::new();
+        let base = world.spawn((Item, Tags(vec!["base"]))).id();
+        let a = world.spawn((Item, Template(base), Tags(vec!["a"]))).id();
+        let b = world.spawn((Item, Template(base), Tags(vec!["b"]))).id();
+        let plain = world.spawn((Item, Template(base))).id();
+        let item = world
+            .spawn((Item, Templates(vec![a, plain, b]), Tags(vec!["item"])))
+            .id();
+
+        let mut state = SystemState::<ItemData<&Tags>>::new(&mut world);
+        let tags = state.get(&world);
+
+        assert_eq!(
+            tags.merged_get(item).unwrap().unwrap().0,
+            ["item", "a", "b", "base"]
+        );
+        assert_eq!(tags.merged_get(plain).unwrap().unwrap().0, ["base"]);
+    }
+}
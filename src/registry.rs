@@ -0,0 +1,82 @@
+//! Stable ids of template items.
+
+use bevy::{
+    ecs::{component::ComponentId, world::DeferredWorld},
+    prelude::*,
+    utils::HashMap,
+};
+
+use crate::{
+    components::{Count, Item, Prototype, Template},
+    events::DuplicateItemId,
+};
+
+pub fn plugin(app: &mut App) {
+    app.init_resource::<ItemRegistry>();
+}
+
+/// Stable id of a template item, e.g. `item::stone`, kept in the [`ItemRegistry`].
+/// Items with an id are [`Prototype`]s.
+#[derive(Component, Reflect, Clone, Debug, PartialEq, Eq, Hash)]
+#[component(on_insert = item_id_inserted, on_replace = item_id_replaced)]
+#[require(Prototype)]
+#[reflect(Component)]
+pub struct ItemId(pub String);
+
+/// Template items by their [`ItemId`].
+#[derive(Resource, Default)]
+pub struct ItemRegistry(HashMap<String, Entity>);
+
+impl ItemRegistry {
+    pub fn get(&self, id: &str) -> Option<Entity> {
+        self.0.get(id).copied()
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.0.contains_key(id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Entity)> {
+        self.0.iter().map(|(id, &entity)| (id.as_str(), entity))
+    }
+
+    /// Spawns an item based on the template with the id, [`None`] if it isn't registered.
+    pub fn spawn_instance(&self, commands: &mut Commands, id: &str, count: u32) -> Option<Entity> {
+        let template = self.get(id)?;
+        Some(
+            commands
+                .spawn((Item, Template(template), Count(count)))
+                .id(),
+        )
+    }
+}
+
+/// Registers the id, the first item with an id keeps it.
+fn item_id_inserted(mut world: DeferredWorld, entity: Entity, _: ComponentId) {
+    let id = world.get::<ItemId>(entity).unwrap().0.clone();
+    let Some(mut registry) = world.get_resource_mut::<ItemRegistry>() else {
+        return;
+    };
+    match registry.0.get(&id) {
+        Some(&registered) if registered != entity => {
+            world.commands().trigger(DuplicateItemId {
+                id,
+                registered,
+                duplicate: entity,
+            });
+        }
+        _ => {
+            registry.0.insert(id, entity);
+        }
+    }
+}
+
+fn item_id_replaced(mut world: DeferredWorld, entity: Entity, _: ComponentId) {
+    let id = world.get::<ItemId>(entity).unwrap().0.clone();
+    let Some(mut registry) = world.get_resource_mut::<ItemRegistry>() else {
+        return;
+    };
+    if registry.get(&id) == Some(entity) {
+        registry.0.remove(&id);
+    }
+}
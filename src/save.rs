@@ -0,0 +1,586 @@
+//! Saving and loading of inventories with their instance items.
+//!
+//! Items are saved with the [`ItemId`] of their templates, their [`Count`]
+//! and reflected components overriding the templates:
+//! ```ron
+//! (
+//!     slots: [
+//!         Some((template: Some("item::stone"), templates: [], count: Some(5), components: {})),
+//!         None,
+//!     ],
+//! )
+//! ```
+
+use std::{any::TypeId, fmt};
+
+use bevy::{
+    ecs::reflect::{ReflectComponent, ReflectMapEntities},
+    prelude::*,
+    reflect::{serde::TypedReflectSerializer, PartialReflect, TypeRegistry},
+};
+use serde::{
+    de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor},
+    ser::{SerializeMap, SerializeStruct},
+    Deserialize, Serialize, Serializer,
+};
+
+use crate::{
+    components::{Count, Inventory, Item, Template, Templates},
+    definitions::ComponentsSeed,
+    events::InventoryChanged,
+    inherit::Inherited,
+    lifecycle::{release_items, ContentsPolicy},
+    registry::{ItemId, ItemRegistry},
+};
+
+/// Slots of an inventory independent of entities.
+pub struct SavedInventory {
+    pub slots: Vec<Option<SavedItem>>,
+}
+
+/// Instance item independent of entities.
+pub struct SavedItem {
+    /// Id of the [`Template`].
+    pub template: Option<String>,
+    /// Ids of the [`Templates`].
+    pub templates: Vec<String>,
+    pub count: Option<u32>,
+    /// Reflected components overriding the templates.
+    pub components: Vec<Box<dyn PartialReflect>>,
+}
+
+/// Reason why an inventory couldn't be saved or loaded.
+#[derive(Debug)]
+pub enum SaveError {
+    /// Entity doesn't exist or has no [`Inventory`].
+    MissingInventory(Entity),
+    /// Template of a saved item has no [`ItemId`].
+    MissingItemId(Entity),
+    /// No template is registered with the id.
+    UnknownItemId(String),
+    /// Saved slots don't match the size of the inventory.
+    SizeMismatch {
+        inventory: Entity,
+        size: usize,
+        saved: usize,
+    },
+    Ron(ron::Error),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingInventory(inventory) => write!(f, "{inventory} is not an inventory"),
+            Self::MissingItemId(template) => write!(f, "template {template} has no item id"),
+            Self::UnknownItemId(id) => write!(f, "no template is registered as {id}"),
+            Self::SizeMismatch {
+                inventory,
+                size,
+                saved,
+            } => write!(
+                f,
+                "inventory {inventory} has {size} slots, but {saved} were saved"
+            ),
+            Self::Ron(error) => write!(f, "couldn't serialize inventory: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<ron::Error> for SaveError {
+    fn from(error: ron::Error) -> Self {
+        Self::Ron(error)
+    }
+}
+
+impl SavedInventory {
+    /// Captures the inventory and its items.
+    pub fn save(world: &World, inventory: Entity) -> Result<Self, SaveError> {
+        let slots = world
+            .get::<Inventory>(inventory)
+            .ok_or(SaveError::MissingInventory(inventory))?;
+        let registry = world.resource::<AppTypeRegistry>().read();
+        let slots = slots
+            .0
+            .iter()
+            .map(|slot| {
+                slot.map(|item| SavedItem::save(world, &registry, item))
+                    .transpose()
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { slots })
+    }
+
+    /// Spawns the saved items into the inventory, replacing its slots.
+    /// Items previously in the inventory are handled by its [`ContentsPolicy`].
+    /// The inventory has to have as many slots as were saved.
+    pub fn load(&self, world: &mut World, inventory: Entity) -> Result<(), SaveError> {
+        let previous: Vec<_> = world
+            .get::<Inventory>(inventory)
+            .ok_or(SaveError::MissingInventory(inventory))?
+            .0
+            .iter()
+            .flatten()
+            .copied()
+            .collect();
+        let size = world.get::<Inventory>(inventory).unwrap().0.len();
+        if size != self.slots.len() {
+            return Err(SaveError::SizeMismatch {
+                inventory,
+                size,
+                saved: self.slots.len(),
+            });
+        }
+        // Resolve all templates first, so nothing is spawned on failure.
+        let registry = world.resource::<ItemRegistry>();
+        let resolve = |id: &String| {
+            registry
+                .get(id)
+                .ok_or_else(|| SaveError::UnknownItemId(id.clone()))
+        };
+        let mut templates = Vec::with_capacity(self.slots.len());
+        for item in self.slots.iter().flatten() {
+            let template = item.template.as_ref().map(resolve).transpose()?;
+            let mixins: Vec<_> = item
+                .templates
+                .iter()
+                .map(resolve)
+                .collect::<Result<_, _>>()?;
+            templates.push((template, mixins));
+        }
+
+        let type_registry = world.resource::<AppTypeRegistry>().clone();
+        let type_registry = type_registry.read();
+        let mut templates = templates.into_iter();
+        let mut slots = Vec::with_capacity(self.slots.len());
+        for slot in &self.slots {
+            let Some(saved) = slot else {
+                slots.push(None);
+                continue;
+            };
+            let (template, mixins) = templates.next().unwrap();
+            let mut item = world.spawn(Item);
+            for component in &saved.components {
+                let reflect_component = component.get_represented_type_info().and_then(|info| {
+                    type_registry.get_type_data::<ReflectComponent>(info.type_id())
+                });
+                if let Some(reflect_component) = reflect_component {
+                    reflect_component.insert(
+                        &mut item,
+                        component.as_partial_reflect(),
+                        &type_registry,
+                    );
+                }
+            }
+            if let Some(count) = saved.count {
+                item.insert(Count(count));
+            }
+            if let Some(template) = template {
+                item.insert(Template(template));
+            }
+            if !mixins.is_empty() {
+                item.insert(Templates(mixins));
+            }
+            slots.push(Some(item.id()));
+        }
+        world.entity_mut(inventory).insert(Inventory(slots));
+        // Previous items are released by the replaced inventory first.
+        world.flush();
+        let policy = ContentsPolicy::of(world, inventory);
+        release_items(world, inventory, previous, policy);
+        world.trigger_targets(InventoryChanged, inventory);
+        Ok(())
+    }
+
+    /// Serializes the inventory into RON.
+    pub fn to_ron(&self, registry: &TypeRegistry) -> Result<String, SaveError> {
+        let serializer = InventorySerializer {
+            inventory: self,
+            registry,
+        };
+        Ok(ron::ser::to_string_pretty(
+            &serializer,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    /// Deserializes the inventory from RON, components have to be registered.
+    pub fn from_ron(registry: &TypeRegistry, ron: &str) -> Result<Self, SaveError> {
+        let mut deserializer = ron::de::Deserializer::from_str(ron).map_err(|e| e.code)?;
+        let inventory = InventorySeed(registry).deserialize(&mut deserializer)?;
+        deserializer.end()?;
+        Ok(inventory)
+    }
+}
+
+impl SavedItem {
+    fn save(world: &World, registry: &TypeRegistry, item: Entity) -> Result<Self, SaveError> {
+        let item_id = |template: Entity| {
+            world
+                .get::<ItemId>(template)
+                .map(|id| id.0.clone())
+                .ok_or(SaveError::MissingItemId(template))
+        };
+        let template = world
+            .get::<Template>(item)
+            .map(|t| item_id(t.0))
+            .transpose()?;
+        let templates = world
+            .get::<Templates>(item)
+            .map(|t| t.0.iter().copied().map(item_id).collect())
+            .transpose()?
+            .unwrap_or_default();
+        Ok(Self {
+            template,
+            templates,
+            count: world.get::<Count>(item).map(|c| c.0),
+            components: overrides(world, registry, item),
+        })
+    }
+}
+
+/// Reflected components of the item which aren't inherited or stored separately.
+/// Components referencing entities, e.g. a nested [`Inventory`] or `Children`, aren't stable across saves
+/// and components which can't be serialized, e.g. an [`Icon`](crate::components::Icon) holding an asset handle,
+/// would fail the whole inventory, so both are left out.
+fn overrides(world: &World, registry: &TypeRegistry, item: Entity) -> Vec<Box<dyn PartialReflect>> {
+    let skipped = [
+        TypeId::of::<Item>(),
+        TypeId::of::<Template>(),
+        TypeId::of::<Templates>(),
+        TypeId::of::<Count>(),
+        TypeId::of::<Inherited>(),
+    ];
+    let inherited = world.get::<Inherited>(item);
+    let Ok(entity) = world.get_entity(item) else {
+        return Vec::new();
+    };
+    entity
+        .archetype()
+        .components()
+        .filter_map(|id| world.components().get_info(id)?.type_id())
+        .filter(|type_id| !skipped.contains(type_id))
+        .filter(|type_id| inherited.is_none_or(|i| !i.0.contains(type_id)))
+        .filter(|&type_id| {
+            registry
+                .get_type_data::<ReflectMapEntities>(type_id)
+                .is_none()
+        })
+        .filter_map(|type_id| {
+            let reflect_component = registry.get_type_data::<ReflectComponent>(type_id)?;
+            let component = reflect_component.reflect(entity)?;
+            let serializable =
+                ron::to_string(&TypedReflectSerializer::new(component, registry)).is_ok();
+            serializable.then(|| component.clone_value())
+        })
+        .collect()
+}
+
+struct InventorySerializer<'a> {
+    inventory: &'a SavedInventory,
+    registry: &'a TypeRegistry,
+}
+
+impl Serialize for InventorySerializer<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let slots: Vec<_> = self
+            .inventory
+            .slots
+            .iter()
+            .map(|slot| {
+                slot.as_ref().map(|item| ItemSerializer {
+                    item,
+                    registry: self.registry,
+                })
+            })
+            .collect();
+        let mut state = serializer.serialize_struct("SavedInventory", 1)?;
+        state.serialize_field("slots", &slots)?;
+        state.end()
+    }
+}
+
+struct ItemSerializer<'a> {
+    item: &'a SavedItem,
+    registry: &'a TypeRegistry,
+}
+
+impl Serialize for ItemSerializer<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let components = ComponentsSerializer {
+            components: &self.item.components,
+            registry: self.registry,
+        };
+        let mut state = serializer.serialize_struct("SavedItem", 4)?;
+        state.serialize_field("template", &self.item.template)?;
+        state.serialize_field("templates", &self.item.templates)?;
+        state.serialize_field("count", &self.item.count)?;
+        state.serialize_field("components", &components)?;
+        state.end()
+    }
+}
+
+struct ComponentsSerializer<'a> {
+    components: &'a [Box<dyn PartialReflect>],
+    registry: &'a TypeRegistry,
+}
+
+impl Serialize for ComponentsSerializer<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.components.len()))?;
+        for component in self.components {
+            // Cloned components are dynamic, their represented type is the one registered.
+            let type_path = component
+                .get_represented_type_info()
+                .map_or(component.reflect_type_path(), |info| info.type_path());
+            map.serialize_entry(
+                type_path,
+                &TypedReflectSerializer::new(component.as_partial_reflect(), self.registry),
+            )?;
+        }
+        map.end()
+    }
+}
+
+#[derive(Clone, Copy)]
+struct InventorySeed<'a>(&'a TypeRegistry);
+
+impl<'de> DeserializeSeed<'de> for InventorySeed<'_> {
+    type Value = SavedInventory;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_struct("SavedInventory", &["slots"], self)
+    }
+}
+
+impl<'de> Visitor<'de> for InventorySeed<'_> {
+    type Value = SavedInventory;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("saved inventory")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut slots = None;
+        while let Some(field) = map.next_key::<InventoryField>()? {
+            match field {
+                InventoryField::Slots => slots = Some(map.next_value_seed(SlotsSeed(self.0))?),
+            }
+        }
+        let slots = slots.ok_or_else(|| de::Error::missing_field("slots"))?;
+        Ok(SavedInventory { slots })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum InventoryField {
+    Slots,
+}
+
+#[derive(Clone, Copy)]
+struct SlotsSeed<'a>(&'a TypeRegistry);
+
+impl<'de> DeserializeSeed<'de> for SlotsSeed<'_> {
+    type Value = Vec<Option<SavedItem>>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for SlotsSeed<'_> {
+    type Value = Vec<Option<SavedItem>>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("list of slots")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut slots = Vec::new();
+        while let Some(slot) = seq.next_element_seed(SlotSeed(self.0))? {
+            slots.push(slot);
+        }
+        Ok(slots)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct SlotSeed<'a>(&'a TypeRegistry);
+
+impl<'de> DeserializeSeed<'de> for SlotSeed<'_> {
+    type Value = Option<SavedItem>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_option(self)
+    }
+}
+
+impl<'de> Visitor<'de> for SlotSeed<'_> {
+    type Value = Option<SavedItem>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("optional saved item")
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        ItemSeed(self.0).deserialize(deserializer).map(Some)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ItemSeed<'a>(&'a TypeRegistry);
+
+const ITEM_FIELDS: &[&str] = &["template", "templates", "count", "components"];
+
+impl<'de> DeserializeSeed<'de> for ItemSeed<'_> {
+    type Value = SavedItem;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_struct("SavedItem", ITEM_FIELDS, self)
+    }
+}
+
+impl<'de> Visitor<'de> for ItemSeed<'_> {
+    type Value = SavedItem;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("saved item")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut item = SavedItem {
+            template: None,
+            templates: Vec::new(),
+            count: None,
+            components: Vec::new(),
+        };
+        while let Some(field) = map.next_key()? {
+            match field {
+                ItemField::Template => item.template = map.next_value()?,
+                ItemField::Templates => item.templates = map.next_value()?,
+                ItemField::Count => item.count = map.next_value()?,
+                ItemField::Components => {
+                    item.components = map.next_value_seed(ComponentsSeed(self.0))?
+                }
+            }
+        }
+        Ok(item)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum ItemField {
+    Template,
+    Templates,
+    Count,
+    Components,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::{Icon, MaxStack};
+
+    fn world() -> World {
+        let mut world = World::new();
+        let registry = AppTypeRegistry::default();
+        {
+            let mut registry = registry.write();
+            registry.register::<Item>();
+            registry.register::<Template>();
+            registry.register::<Templates>();
+            registry.register::<Count>();
+            registry.register::<MaxStack>();
+            registry.register::<ItemId>();
+            registry.register::<Icon>();
+            registry.register::<Inventory>();
+        }
+        world.insert_resource(registry);
+        world.init_resource::<ItemRegistry>();
+        world
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut world = world();
+        let stone = world
+            .spawn((Item, ItemId("item::stone".into()), MaxStack(8)))
+            .id();
+        let item = world
+            .spawn((Item, Template(stone), Count(5), MaxStack(3)))
+            .id();
+        let inventory = world.spawn(Inventory(vec![Some(item), None])).id();
+        world.flush();
+
+        let saved = SavedInventory::save(&world, inventory).unwrap();
+        let ron = saved
+            .to_ron(&world.resource::<AppTypeRegistry>().read())
+            .unwrap();
+        let loaded =
+            SavedInventory::from_ron(&world.resource::<AppTypeRegistry>().read(), &ron).unwrap();
+        loaded.load(&mut world, inventory).unwrap();
+        world.flush();
+
+        let slots = world.get::<Inventory>(inventory).unwrap().0.clone();
+        let [Some(loaded), None] = slots[..] else {
+            panic!("unexpected slots {slots:?}");
+        };
+        assert_ne!(loaded, item);
+        assert!(world.get_entity(item).is_err());
+        assert_eq!(world.get::<Template>(loaded).map(|t| t.0), Some(stone));
+        assert_eq!(world.get::<Count>(loaded).map(|c| c.0), Some(5));
+        assert_eq!(world.get::<MaxStack>(loaded).map(|m| m.0), Some(3));
+    }
+
+    #[test]
+    fn size_mismatch() {
+        let mut world = world();
+        let inventory = world.spawn(Inventory::with_size(2)).id();
+        let saved = SavedInventory::save(&world, inventory).unwrap();
+        let smaller = world.spawn(Inventory::with_size(1)).id();
+
+        let result = saved.load(&mut world, smaller);
+
+        assert!(matches!(
+            result,
+            Err(SaveError::SizeMismatch {
+                size: 1,
+                saved: 2,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn skips_handles_and_entities() {
+        let mut world = world();
+        let stone = world.spawn((Item, ItemId("item::stone".into()))).id();
+        let icon = Assets::<Image>::default().add(Image::default());
+        let content = world.spawn(Item).id();
+        let bag = world
+            .spawn((
+                Item,
+                Template(stone),
+                Icon(icon),
+                Inventory(vec![Some(content)]),
+                MaxStack(3),
+            ))
+            .id();
+        let inventory = world.spawn(Inventory(vec![Some(bag)])).id();
+        world.flush();
+
+        let saved = SavedInventory::save(&world, inventory).unwrap();
+        let ron = saved.to_ron(&world.resource::<AppTypeRegistry>().read());
+
+        let ron = ron.unwrap();
+        assert!(ron.contains("MaxStack"));
+        assert!(!ron.contains("Icon") && !ron.contains("Inventory"));
+    }
+}
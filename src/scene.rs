@@ -0,0 +1,119 @@
+//! Authoring inventories and item templates as [`DynamicScene`]s, e.g. in `.scn.ron` files.
+//!
+//! [`Inventory`], [`Template`] and [`Templates`] reflect `MapEntities`,
+//! so their entities are remapped when the scene is written to a world.
+//! [`DerivedItems`](crate::components::DerivedItems) and the [`ItemRegistry`](crate::registry::ItemRegistry)
+//! are rebuilt by hooks as the components are inserted.
+
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{components::Inventory, inherit::Inherited, params::resolution_order};
+
+/// Scene of the inventories with their items and all templates of these items.
+///
+/// Components copied from templates by [`FlattenTemplates`](crate::inherit::FlattenTemplates)
+/// are left out, so they don't turn into overrides when the scene is spawned.
+pub fn inventory_scene(
+    world: &World,
+    inventories: impl IntoIterator<Item = Entity>,
+) -> DynamicScene {
+    let mut entities = Vec::new();
+    let mut visited = HashSet::new();
+    for inventory in inventories {
+        let items = world
+            .get::<Inventory>(inventory)
+            .into_iter()
+            .flat_map(|inventory| inventory.0.iter().flatten().copied());
+        for item in items {
+            let order = resolution_order(world, item).unwrap_or_else(|_| vec![item]);
+            // Templates first, so they exist by the time items are linked to them.
+            for entity in order.into_iter().rev() {
+                if visited.insert(entity) {
+                    entities.push(entity);
+                }
+            }
+        }
+        if visited.insert(inventory) {
+            entities.push(inventory);
+        }
+    }
+
+    let mut scene = DynamicSceneBuilder::from_world(world)
+        .extract_entities(entities.into_iter())
+        .build();
+    for entity in &mut scene.entities {
+        let Some(inherited) = world.get::<Inherited>(entity.entity) else {
+            continue;
+        };
+        entity.components.retain(|component| {
+            component
+                .get_represented_type_info()
+                .is_none_or(|info| !inherited.0.contains(&info.type_id()))
+        });
+    }
+    scene
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::scene::serde::SceneDeserializer;
+    use serde::de::DeserializeSeed;
+
+    use super::*;
+    use crate::components::{Count, Item, MaxStack, Template};
+
+    fn world() -> World {
+        let mut world = World::new();
+        let registry = AppTypeRegistry::default();
+        {
+            let mut registry = registry.write();
+            registry.register::<Item>();
+            registry.register::<Template>();
+            registry.register::<Count>();
+            registry.register::<MaxStack>();
+            registry.register::<Inventory>();
+        }
+        world.insert_resource(registry);
+        world
+    }
+
+    #[test]
+    fn round_trip_remaps_slots() {
+        let mut world = world();
+        let template = world.spawn((Item, MaxStack(8))).id();
+        let item = world.spawn((Item, Template(template), Count(5))).id();
+        let inventory = world.spawn(Inventory(vec![None, Some(item)])).id();
+        world.flush();
+        let registry = world.resource::<AppTypeRegistry>().clone();
+        let ron = inventory_scene(&world, [inventory])
+            .serialize(&registry.read())
+            .unwrap();
+
+        let mut loaded = self::world();
+        // Occupy the saved ids, so entities have to be remapped.
+        for _ in 0..3 {
+            loaded.spawn_empty();
+        }
+        let mut deserializer = ron::de::Deserializer::from_str(&ron).unwrap();
+        let scene = SceneDeserializer {
+            type_registry: &registry.read(),
+        }
+        .deserialize(&mut deserializer)
+        .unwrap();
+        let mut entity_map = Default::default();
+        scene.write_to_world(&mut loaded, &mut entity_map).unwrap();
+        loaded.flush();
+
+        let (inventory, item, template) = (
+            entity_map[&inventory],
+            entity_map[&item],
+            entity_map[&template],
+        );
+        assert_eq!(
+            loaded.get::<Inventory>(inventory).unwrap().0,
+            [None, Some(item)]
+        );
+        assert_eq!(loaded.get::<Template>(item).map(|t| t.0), Some(template));
+        assert_eq!(loaded.get::<Count>(item).map(|c| c.0), Some(5));
+    }
+}
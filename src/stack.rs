@@ -0,0 +1,140 @@
+//! Stacking of items with [`Count`].
+
+use bevy::{
+    ecs::{
+        reflect::{ReflectComponent, ReflectMapEntities},
+        system::SystemState,
+    },
+    prelude::*,
+    reflect::{PartialReflect, TypeRegistry},
+};
+
+use crate::{
+    components::{Count, Item, MaxStack, Template, Templates},
+    inherit::Inherited,
+    params::{parents, ItemData},
+};
+
+pub fn plugin(app: &mut App) {
+    app.init_resource::<StackRule>();
+}
+
+/// Predicate deciding whether two items can share a single stack.
+/// Only called for distinct items which both have a [`Count`].
+#[derive(Resource)]
+pub struct StackRule(pub fn(&World, Entity, Entity) -> bool);
+
+impl Default for StackRule {
+    fn default() -> Self {
+        Self(same_template)
+    }
+}
+
+/// Default stacking rule, items can stack if they are based on the same templates.
+/// Both [`Template`] and [`Templates`] are compared, items without any template don't stack.
+pub fn same_template(world: &World, a: Entity, b: Entity) -> bool {
+    let parents = |entity| parents(world.get(entity), world.get(entity));
+    let parents_a = parents(a);
+    !parents_a.is_empty() && parents_a == parents(b)
+}
+
+/// Whether items `a` and `b` can be merged into a single stack.
+pub fn can_stack(world: &World, a: Entity, b: Entity) -> bool {
+    if a == b || world.get::<Count>(a).is_none() || world.get::<Count>(b).is_none() {
+        return false;
+    }
+    let rule = world
+        .get_resource::<StackRule>()
+        .map(|r| r.0)
+        .unwrap_or(same_template);
+    rule(world, a, b)
+}
+
+/// Maximum stack size of an item, falls back to template's [`MaxStack`].
+pub fn max_stack(world: &mut World, item: Entity) -> u32 {
+    let mut state = SystemState::<ItemData<&MaxStack>>::new(world);
+    let max_stacks = state.get(world);
+    match max_stacks.extended_get(item) {
+        Ok(Some(max_stack)) => max_stack.0,
+        _ => u32::MAX,
+    }
+}
+
+/// Moves up to `amount` from the `from` stack into the `to` stack, limited by its [`MaxStack`].
+/// Returns the moved amount, the remainder stays in `from`.
+pub fn merge_stacks(world: &mut World, from: Entity, to: Entity, amount: u32) -> u32 {
+    if !can_stack(world, from, to) {
+        return 0;
+    }
+    let max = max_stack(world, to);
+    let available = world.get::<Count>(from).unwrap().0.min(amount);
+    let mut target = world.get_mut::<Count>(to).unwrap();
+    let moved = available.min(max.saturating_sub(target.0));
+    target.0 += moved;
+    world.get_mut::<Count>(from).unwrap().0 -= moved;
+    moved
+}
+
+/// Splits off `amount` from the stack into a new item.
+/// The new item shares the [`Template`] and [`Templates`] and gets copies of the stack's reflected components,
+/// e.g. [`Rotated`](crate::grid::Rotated) or overrides of the templates.
+/// Other components referencing entities, such as `Children`, aren't copied.
+/// Returns [`None`] if the item has no [`Count`] or the amount doesn't leave a remainder.
+pub fn split_stack(world: &mut World, item: Entity, amount: u32) -> Option<Entity> {
+    let mut count = world.get_mut::<Count>(item)?;
+    if amount == 0 || amount >= count.0 {
+        return None;
+    }
+    count.0 -= amount;
+    let template = world.get::<Template>(item).map(|t| t.0);
+    let templates = world.get::<Templates>(item).cloned();
+    let inherited = world.get::<Inherited>(item).map(|i| i.0.clone());
+    let registry = world.get_resource::<AppTypeRegistry>().cloned();
+    let components = registry
+        .as_ref()
+        .map(|registry| reflected_components(world, &registry.read(), item))
+        .unwrap_or_default();
+
+    let mut split = world.spawn(Item);
+    if let Some(registry) = registry {
+        let registry = registry.read();
+        for (reflect_component, component) in components {
+            reflect_component.insert(&mut split, component.as_partial_reflect(), &registry);
+        }
+    }
+    split.insert(Count(amount));
+    if let Some(template) = template {
+        split.insert(Template(template));
+    }
+    if let Some(templates) = templates {
+        split.insert(templates);
+    }
+    if let Some(inherited) = inherited {
+        split.insert(Inherited(inherited));
+    }
+    Some(split.id())
+}
+
+/// Copies of the item's reflected components which don't reference other entities.
+fn reflected_components(
+    world: &World,
+    registry: &TypeRegistry,
+    item: Entity,
+) -> Vec<(ReflectComponent, Box<dyn PartialReflect>)> {
+    let entity = world.entity(item);
+    entity
+        .archetype()
+        .components()
+        .filter_map(|id| world.components().get_info(id)?.type_id())
+        .filter(|&type_id| {
+            registry
+                .get_type_data::<ReflectMapEntities>(type_id)
+                .is_none()
+        })
+        .filter_map(|type_id| {
+            let reflect_component = registry.get_type_data::<ReflectComponent>(type_id)?;
+            let component = reflect_component.reflect(entity)?.clone_value();
+            Some((reflect_component.clone(), component))
+        })
+        .collect()
+}
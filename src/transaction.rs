@@ -0,0 +1,181 @@
+//! Buffered inventory operations applied in a deterministic order.
+
+use bevy::prelude::*;
+
+use crate::{
+    events::InventoryOperationFailed,
+    ops::{InventoryError, InventoryOps, InventoryWorldExt},
+};
+
+pub fn plugin(app: &mut App) {
+    app.init_resource::<InventoryTransactions>();
+    app.add_systems(
+        PostUpdate,
+        apply_transactions.in_set(InventorySystem::ApplyTransactions),
+    );
+}
+
+/// System sets of inventory systems.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum InventorySystem {
+    /// Applies queued [`InventoryTransactions`].
+    ApplyTransactions,
+}
+
+/// Custom operation with access to [`InventoryOps`].
+pub type CustomOperation =
+    Box<dyn FnOnce(&mut InventoryOps) -> Result<(), InventoryError> + Send + Sync>;
+
+/// Single step of an [`InventoryTransaction`], see [`InventoryOps`] for their behavior.
+pub enum InventoryOperation {
+    Insert {
+        inventory: Entity,
+        index: usize,
+        item: Entity,
+    },
+    Remove {
+        inventory: Entity,
+        index: usize,
+    },
+    Move {
+        inventory_a: Entity,
+        index_a: usize,
+        inventory_b: Entity,
+        index_b: usize,
+    },
+    Swap {
+        inventory_a: Entity,
+        index_a: usize,
+        inventory_b: Entity,
+        index_b: usize,
+    },
+    Merge {
+        inventory_a: Entity,
+        index_a: usize,
+        inventory_b: Entity,
+        index_b: usize,
+        amount: u32,
+    },
+    Split {
+        inventory_a: Entity,
+        index_a: usize,
+        inventory_b: Entity,
+        index_b: usize,
+        amount: u32,
+    },
+    TransferAll {
+        inventory_a: Entity,
+        inventory_b: Entity,
+    },
+    Rotate {
+        inventory: Entity,
+        index: usize,
+    },
+    /// Operation deciding what to do once it's applied, e.g. based on slot contents.
+    Custom(CustomOperation),
+}
+
+impl InventoryOperation {
+    pub fn custom(
+        f: impl FnOnce(&mut InventoryOps) -> Result<(), InventoryError> + Send + Sync + 'static,
+    ) -> Self {
+        Self::Custom(Box::new(f))
+    }
+
+    pub fn apply(self, ops: &mut InventoryOps) -> Result<(), InventoryError> {
+        match self {
+            Self::Insert {
+                inventory,
+                index,
+                item,
+            } => ops.insert(inventory, index, item),
+            Self::Remove { inventory, index } => ops.remove(inventory, index).map(drop),
+            Self::Move {
+                inventory_a,
+                index_a,
+                inventory_b,
+                index_b,
+            } => ops
+                .move_item(inventory_a, index_a, inventory_b, index_b)
+                .map(drop),
+            Self::Swap {
+                inventory_a,
+                index_a,
+                inventory_b,
+                index_b,
+            } => ops.swap(inventory_a, index_a, inventory_b, index_b),
+            Self::Merge {
+                inventory_a,
+                index_a,
+                inventory_b,
+                index_b,
+                amount,
+            } => ops
+                .merge(inventory_a, index_a, inventory_b, index_b, amount)
+                .map(drop),
+            Self::Split {
+                inventory_a,
+                index_a,
+                inventory_b,
+                index_b,
+                amount,
+            } => ops
+                .split(inventory_a, index_a, inventory_b, index_b, amount)
+                .map(drop),
+            Self::TransferAll {
+                inventory_a,
+                inventory_b,
+            } => ops.transfer_all(inventory_a, inventory_b).map(drop),
+            Self::Rotate { inventory, index } => ops.rotate(inventory, index),
+            Self::Custom(f) => f(ops),
+        }
+    }
+}
+
+/// Operations applied all-or-nothing, if any of them fails none of them are applied.
+#[derive(Default)]
+pub struct InventoryTransaction(pub Vec<InventoryOperation>);
+
+impl InventoryTransaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends an operation to the transaction.
+    pub fn with(mut self, operation: InventoryOperation) -> Self {
+        self.0.push(operation);
+        self
+    }
+}
+
+impl From<InventoryOperation> for InventoryTransaction {
+    fn from(operation: InventoryOperation) -> Self {
+        Self(vec![operation])
+    }
+}
+
+/// Queue of transactions, applied in order during [`InventorySystem::ApplyTransactions`].
+#[derive(Resource, Default)]
+pub struct InventoryTransactions(Vec<InventoryTransaction>);
+
+impl InventoryTransactions {
+    pub fn push(&mut self, transaction: impl Into<InventoryTransaction>) {
+        self.0.push(transaction.into());
+    }
+}
+
+/// Applies all queued transactions, failures are reported with [`InventoryOperationFailed`].
+pub fn apply_transactions(world: &mut World) {
+    let transactions = std::mem::take(&mut world.resource_mut::<InventoryTransactions>().0);
+    for transaction in transactions {
+        let result = world.inventory_ops().transaction(|ops| {
+            transaction
+                .0
+                .into_iter()
+                .try_for_each(|operation| operation.apply(ops))
+        });
+        if let Err(error) = result {
+            world.trigger(InventoryOperationFailed(error));
+        }
+    }
+}
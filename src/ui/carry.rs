@@ -2,11 +2,13 @@ use std::marker::PhantomData;
 
 use bevy::{picking::pointer::PointerId, prelude::*, window::PrimaryWindow};
 
-use crate::components::Inventory;
+use crate::{
+    components::Inventory,
+    history::UntrackedInventory,
+    transaction::{InventoryOperation, InventoryTransactions},
+};
 
 use super::{
-    change_propagation::InventoryChanged,
-    item::spawn_item,
     layout::CenterPosition,
     prelude::{InventoryUi, SlotChanged},
     slot::SlotUi,
@@ -17,22 +19,26 @@ pub fn plugin(app: &mut App) {
     app.add_systems(Update, carry_follow_mouse);
     app.add_observer(carry_interact);
     app.add_observer(swap);
+    app.add_observer(merge);
+    app.add_observer(split);
     app.add_observer(carry_despawn);
 }
 
+/// Temporary 1-slot inventory following a pointer.
+/// Picking up and placing an item is recorded as a single step, see [`UntrackedInventory`].
 #[derive(Component)]
+#[require(UntrackedInventory)]
 pub struct Carry {
     pub pointer_id: PointerId,
-    pub item: Entity,
 }
 
 fn carry_start(
     trigger: Trigger<Pointer<Down>>,
     mut commands: Commands,
-    mut slots: Query<&SlotUi>,
+    slots: Query<&SlotUi>,
     carriers: Query<&Carry>,
     window: Single<&Window, With<PrimaryWindow>>,
-    mut inventories: Query<&mut Inventory>,
+    inventories: Query<&Inventory>,
 ) {
     let pointer_id = trigger.pointer_id;
     let pointer_in_use = !carriers.iter().all(|c| c.pointer_id != pointer_id);
@@ -42,28 +48,33 @@ fn carry_start(
     let Some(cursor) = window.cursor_position() else {
         return;
     };
-    let Ok(slot) = slots.get_mut(trigger.entity()) else {
+    let Ok(slot) = slots.get(trigger.entity()) else {
         return;
     };
-
-    let mut inventory = inventories.get_mut(slot.inventory).unwrap();
-    let Some(item) = inventory.0[slot.index].take() else {
+    if !matches!(
+        trigger.button,
+        PointerButton::Primary | PointerButton::Secondary
+    ) {
         return;
-    };
-    commands.trigger_targets(InventoryChanged, slot.inventory);
+    }
+    let occupied = inventories
+        .get(slot.inventory)
+        .is_ok_and(|inventory| inventory.0.get(slot.index).is_some_and(Option::is_some));
+    if !occupied {
+        return;
+    }
 
-    let mut inventory = Inventory::new::<1>();
-    inventory.0[0] = Some(item);
+    let carry = commands
+        .spawn((
+            Inventory::new::<1>(),
+            Carry { pointer_id },
+            CenterPosition { position: cursor },
+            PickingBehavior::IGNORE,
+            Node::default(),
+            GlobalZIndex(2),
+        ))
+        .id();
     commands.queue(move |world: &mut World| {
-        let carry = world
-            .spawn((
-                inventory,
-                Carry { pointer_id, item },
-                CenterPosition { position: cursor },
-                PickingBehavior::IGNORE,
-                Node::default(),
-            ))
-            .id();
         let inventory = world
             .spawn((
                 InventoryUi { data: carry },
@@ -72,22 +83,32 @@ fn carry_start(
             ))
             .set_parent(carry)
             .id();
-        let slot = world
+        world
             .spawn((
                 SlotUi {
-                    data: Some(item),
+                    data: None,
                     inventory: carry,
                     index: 0,
                 },
                 PickingBehavior::IGNORE,
                 Node::default(),
             ))
-            .set_parent(inventory)
-            .id();
-        spawn_item(world, item)
-            .insert(PickingBehavior::IGNORE)
-            .set_parent(slot);
+            .set_parent(inventory);
     });
+
+    // Actions fill the empty carrier from the clicked slot.
+    let data = InteractionData::new(carry, 0, slot.inventory, slot.index);
+    if trigger.button == PointerButton::Primary {
+        commands.trigger(Interaction::<Swap> {
+            action: PhantomData,
+            data,
+        });
+    } else {
+        commands.trigger(Interaction::<Split> {
+            action: PhantomData,
+            data,
+        });
+    }
 }
 
 fn carry_follow_mouse(
@@ -121,12 +142,18 @@ fn carry_interact(
     let Ok(slot) = slots.get(slot_entity) else {
         return;
     };
-    #[allow(clippy::single_match)]
+    let data = InteractionData::new(carry_entity, 0, slot.inventory, slot.index);
     match trigger.button {
         PointerButton::Primary => {
-            commands.trigger(Interaction::<Swap> {
+            commands.trigger(Interaction::<Merge> {
+                action: PhantomData,
+                data,
+            });
+        }
+        PointerButton::Secondary => {
+            commands.trigger(Interaction::<Split> {
                 action: PhantomData,
-                data: InteractionData::new(carry_entity, 0, slot.inventory, slot.index),
+                data,
             });
         }
         _ => {}
@@ -139,6 +166,7 @@ pub struct Interaction<A: Action> {
     pub data: InteractionData,
 }
 
+#[derive(Clone, Copy)]
 pub enum InteractionData {
     SameSlot {
         inventory: Entity,
@@ -158,6 +186,24 @@ pub enum InteractionData {
 }
 
 impl InteractionData {
+    /// Both slots of an interaction, [`None`] if they are the same slot.
+    pub fn slots(&self) -> Option<(Entity, usize, Entity, usize)> {
+        match *self {
+            Self::SameSlot { .. } => None,
+            Self::SameInventory {
+                inventory,
+                index_a,
+                index_b,
+            } => Some((inventory, index_a, inventory, index_b)),
+            Self::Different {
+                inventory_a,
+                index_a,
+                inventory_b,
+                index_b,
+            } => Some((inventory_a, index_a, inventory_b, index_b)),
+        }
+    }
+
     pub fn new(inventory_a: Entity, index_a: usize, inventory_b: Entity, index_b: usize) -> Self {
         if inventory_a == inventory_b {
             if index_a == index_b {
@@ -189,42 +235,67 @@ pub struct Swap;
 
 impl Action for Swap {}
 
-pub fn swap(
-    trigger: Trigger<Interaction<Swap>>,
-    mut inventories: Query<&mut Inventory>,
-    mut commands: Commands,
+pub fn swap(trigger: Trigger<Interaction<Swap>>, mut transactions: ResMut<InventoryTransactions>) {
+    let Some((inventory_a, index_a, inventory_b, index_b)) = trigger.data.slots() else {
+        return;
+    };
+    transactions.push(InventoryOperation::Swap {
+        inventory_a,
+        index_a,
+        inventory_b,
+        index_b,
+    });
+}
+
+/// Merges stack from slot `a` into the stack in slot `b`, the remainder stays in slot `a`.
+/// Falls back to [`Swap`] if the items can't be stacked.
+pub struct Merge;
+
+impl Action for Merge {}
+
+pub fn merge(
+    trigger: Trigger<Interaction<Merge>>,
+    mut transactions: ResMut<InventoryTransactions>,
 ) {
-    match trigger.data {
-        InteractionData::SameSlot {
-            inventory: _,
-            index: _,
-        } => {}
-        InteractionData::SameInventory {
-            inventory,
-            index_a,
-            index_b,
-        } => {
-            let mut inv_data = inventories.get_mut(inventory).unwrap();
-            let (a, b) = inv_data.0.split_at_mut(index_a.max(index_b));
-            let (slot_a, slot_b) = (&mut a[index_a.min(index_b)], &mut b[0]);
-            std::mem::swap(slot_a, slot_b);
-            commands.trigger_targets(InventoryChanged, inventory);
+    let Some((inventory_a, index_a, inventory_b, index_b)) = trigger.data.slots() else {
+        return;
+    };
+    transactions.push(InventoryOperation::custom(move |ops| {
+        if !ops.can_merge(inventory_a, index_a, inventory_b, index_b) {
+            return ops.swap(inventory_a, index_a, inventory_b, index_b);
         }
-        InteractionData::Different {
-            inventory_a,
-            index_a,
-            inventory_b,
-            index_b,
-        } => {
-            let [mut inv_data_a, mut inv_data_b] = inventories
-                .get_many_mut([inventory_a, inventory_b])
-                .unwrap();
-            let (slot_a, slot_b) = (&mut inv_data_a.0[index_a], &mut inv_data_b.0[index_b]);
-            std::mem::swap(slot_a, slot_b);
-            commands.trigger_targets(InventoryChanged, inventory_a);
-            commands.trigger_targets(InventoryChanged, inventory_b);
+        ops.merge(inventory_a, index_a, inventory_b, index_b, u32::MAX)?;
+        Ok(())
+    }));
+}
+
+/// Splits stacks between slot `a` and slot `b`.
+/// If slot `a` is empty, it takes half of the stack from slot `b`,
+/// otherwise a single unit is deposited from slot `a` into slot `b`.
+/// Items without [`Count`](crate::components::Count) are moved whole.
+pub struct Split;
+
+impl Action for Split {}
+
+pub fn split(
+    trigger: Trigger<Interaction<Split>>,
+    mut transactions: ResMut<InventoryTransactions>,
+) {
+    let Some((inventory_a, index_a, inventory_b, index_b)) = trigger.data.slots() else {
+        return;
+    };
+    transactions.push(InventoryOperation::custom(move |ops| {
+        if ops.get(inventory_a, index_a)?.is_some() {
+            ops.split(inventory_a, index_a, inventory_b, index_b, 1)?;
+            return Ok(());
         }
-    }
+        let Some(item) = ops.get(inventory_b, index_b)? else {
+            return Ok(());
+        };
+        let half = ops.count(item).div_ceil(2);
+        ops.split(inventory_b, index_b, inventory_a, index_a, half)?;
+        Ok(())
+    }));
 }
 
 pub fn carry_despawn(
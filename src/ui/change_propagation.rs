@@ -1,7 +1,9 @@
 use bevy::prelude::*;
 
 use crate::{
-    components::{Inventory, Item, Template},
+    components::Inventory,
+    events::{InventoryChanged, InventoryOperationFailed, ItemChanged},
+    ops::InventoryError,
     ui::{
         inventory::InventoryUi,
         item::{spawn_item, ItemUi},
@@ -15,15 +17,6 @@ pub fn plugin(app: &mut App) {
     app.add_observer(slot_changed);
 }
 
-/// Event emitted when contents of an inventory change.
-#[derive(Event)]
-pub struct InventoryChanged;
-
-/// Event emitted when item descriptor changes.
-/// It's also emitted if any of the extended items change.
-#[derive(Event)]
-pub struct ItemChanged;
-
 /// Event emitted when
 #[derive(Event)]
 pub struct SlotChanged(pub Option<Entity>);
@@ -36,7 +29,10 @@ fn inventory_changed(
     mut commands: Commands,
 ) {
     let inv_entity = trigger.entity();
-    let inv = invs.get(inv_entity).unwrap();
+    // Events can arrive after the inventory was despawned, its UIs are cleaned up separately.
+    let Ok(inv) = invs.get(inv_entity) else {
+        return;
+    };
     // For all UIs of this inventory.
     for (inv_ui, children) in &inv_uis {
         if inv_ui.data != inv_entity {
@@ -47,7 +43,13 @@ fn inventory_changed(
             let Ok(slot) = slot_uis.get(child) else {
                 continue;
             };
-            let content = inv.0[slot.index];
+            let Some(&content) = inv.0.get(slot.index) else {
+                commands.trigger(InventoryOperationFailed(InventoryError::IndexOutOfBounds {
+                    inventory: inv_entity,
+                    index: slot.index,
+                }));
+                continue;
+            };
             if slot.data != content {
                 commands.trigger_targets(SlotChanged(content), child);
             }
@@ -58,10 +60,8 @@ fn inventory_changed(
 fn item_changed(
     trigger: Trigger<ItemChanged>,
     slot_uis: Query<(Entity, &SlotUi)>,
-    items: Query<(Entity, &Template), With<Item>>,
     mut commands: Commands,
 ) {
-    let item = trigger.entity();
     let slot = Some(trigger.entity());
     // Update slots that contain this item.
     for (slot_entity, slot_ui) in &slot_uis {
@@ -70,12 +70,6 @@ fn item_changed(
         }
         commands.trigger_targets(SlotChanged(slot), slot_entity);
     }
-    // Update items that depend on this item.
-    for (entity, extends) in &items {
-        if extends.0 == item {
-            commands.trigger_targets(ItemChanged, entity);
-        }
-    }
 }
 
 fn slot_changed(
@@ -86,7 +80,9 @@ fn slot_changed(
 ) {
     let slot_entity = trigger.entity();
     let content = trigger.event().0;
-    let (mut slot, children, picking) = slots.get_mut(slot_entity).unwrap();
+    let Ok((mut slot, children, picking)) = slots.get_mut(slot_entity) else {
+        return;
+    };
     slot.data = content;
     if let Some(children) = children {
         for &child in children {
@@ -98,6 +94,10 @@ fn slot_changed(
     let picking = picking.cloned();
     if let Some(item) = content {
         commands.queue(move |world: &mut World| {
+            // Slot could've been despawned with the UI of a removed inventory.
+            if world.get_entity(slot_entity).is_err() {
+                return;
+            }
             let mut item = spawn_item(world, item);
             item.set_parent(slot_entity);
             if let Some(picking) = picking {
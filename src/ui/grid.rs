@@ -0,0 +1,108 @@
+//! UI representation of grid inventories.
+
+use bevy::{ecs::system::SystemState, prelude::*, ui::UiSystem};
+
+use crate::{
+    components::Inventory,
+    events::InventoryOperationFailed,
+    grid::{GridInventory, Rotated, Shape},
+    ops::InventoryError,
+    params::ItemData,
+};
+
+use super::{
+    inventory::InventoryUi,
+    item::ItemUi,
+    slot::{SlotUi, SpawnSlotUi},
+    ITEM_SIZE, SLOT_SIZE,
+};
+
+pub fn plugin(app: &mut App) {
+    app.add_systems(PostUpdate, grid_item_layout.before(UiSystem::Layout));
+}
+
+/// Marks [`InventoryUi`] of a [`GridInventory`].
+#[derive(Component)]
+pub struct GridInventoryUi;
+
+/// Command for spawning UI of a [`GridInventory`], slots are laid out in rows.
+pub struct SpawnGridInventoryUi {
+    /// UI entity this will belong to.
+    pub parent: Entity,
+    /// Absolute offset in relation to parent entity.
+    pub offset: Vec2,
+    /// Inventory data.
+    pub inventory: InventoryUi,
+}
+
+impl Command for SpawnGridInventoryUi {
+    fn apply(self, world: &mut World) {
+        let mut state = SystemState::<(Commands, Query<(&Inventory, &GridInventory)>)>::new(world);
+        let (mut commands, inventories) = state.get(world);
+        let data = self.inventory.data;
+        let Ok((inventory, grid)) = inventories.get(data) else {
+            commands.trigger(InventoryOperationFailed(InventoryError::MissingInventory(
+                data,
+            )));
+            state.apply(world);
+            return;
+        };
+        let root = commands
+            .spawn((
+                self.inventory,
+                GridInventoryUi,
+                Node {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(self.offset.x),
+                    top: Val::Px(self.offset.y),
+                    ..default()
+                },
+            ))
+            .id();
+        for (i, &content) in inventory.0.iter().enumerate() {
+            let cell = grid.cell(i).as_vec2();
+            commands.queue(SpawnSlotUi {
+                parent: root,
+                offset: cell * SLOT_SIZE,
+                slot: SlotUi {
+                    data: content,
+                    inventory: data,
+                    index: i,
+                },
+            });
+        }
+        state.apply(world);
+    }
+}
+
+/// Stretches items in grid inventories over all cells of their [`Shape`].
+fn grid_item_layout(
+    mut items: Query<(Entity, &ItemUi, &Parent, &mut Node), Added<ItemUi>>,
+    slots: Query<&SlotUi>,
+    grids: Query<(), With<GridInventory>>,
+    shapes: ItemData<&Shape>,
+    rotated: Query<(), With<Rotated>>,
+    mut commands: Commands,
+) {
+    for (entity, item_ui, parent, mut node) in &mut items {
+        let Ok(slot) = slots.get(parent.get()) else {
+            continue;
+        };
+        if !grids.contains(slot.inventory) {
+            continue;
+        }
+        let size = match shapes.extended_get(item_ui.item) {
+            Ok(Some(shape)) => shape.size(rotated.contains(item_ui.item)),
+            _ => UVec2::ONE,
+        };
+        let margin = (SLOT_SIZE - ITEM_SIZE) / 2.0;
+        let size = size.as_vec2() * SLOT_SIZE - 2.0 * margin;
+        node.position_type = PositionType::Absolute;
+        node.left = Val::Px(margin);
+        node.top = Val::Px(margin);
+        node.width = Val::Px(size.x);
+        node.height = Val::Px(size.y);
+        // Draw above the slots the item spans.
+        commands.entity(entity).insert(GlobalZIndex(1));
+    }
+}
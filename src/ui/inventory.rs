@@ -2,13 +2,18 @@
 
 use bevy::{ecs::system::SystemState, prelude::*};
 
-use crate::components::Inventory;
+use crate::{components::Inventory, events::InventoryOperationFailed, ops::InventoryError};
 
 use super::{
+    carry::Carry,
     slot::{SlotUi, SpawnSlotUi},
     SLOT_SIZE,
 };
 
+pub fn plugin(app: &mut App) {
+    app.add_observer(inventory_removed);
+}
+
 /// UI element representing the underlying inventory.
 #[derive(Component)]
 pub struct InventoryUi {
@@ -31,7 +36,13 @@ impl Command for SpawnInventoryUi {
         let mut state = SystemState::<(Commands, Query<&Inventory>)>::new(world);
         let (mut commands, inventories) = state.get(world);
         let data = self.inventory.data;
-        let inventory = inventories.get(data).unwrap();
+        let Ok(inventory) = inventories.get(data) else {
+            commands.trigger(InventoryOperationFailed(InventoryError::MissingInventory(
+                data,
+            )));
+            state.apply(world);
+            return;
+        };
         let size = inventory.0.len();
         let root = commands
             .spawn((
@@ -59,3 +70,21 @@ impl Command for SpawnInventoryUi {
         state.apply(world);
     }
 }
+
+/// Despawns UIs of the removed inventory, including the carrier if it was one.
+fn inventory_removed(
+    trigger: Trigger<OnRemove, Inventory>,
+    inventory_uis: Query<(Entity, &InventoryUi)>,
+    carriers: Query<(), With<Carry>>,
+    mut commands: Commands,
+) {
+    let inventory = trigger.entity();
+    for (entity, inventory_ui) in &inventory_uis {
+        if inventory_ui.data == inventory {
+            commands.entity(entity).try_despawn_recursive();
+        }
+    }
+    if carriers.contains(inventory) {
+        commands.entity(inventory).try_despawn_recursive();
+    }
+}
@@ -30,21 +30,21 @@ impl Command for SpawnItemUi {
     }
 }
 
-pub fn spawn_item(world: &mut World, item: Entity) -> EntityWorldMut {
+pub fn spawn_item(world: &mut World, item: Entity) -> EntityWorldMut<'_> {
     let mut state = SystemState::<(Commands, ItemData<&Icon>, ItemData<&Count>)>::new(world);
     let (mut commands, icons, counts) = state.get(world);
-    let icon = icons.extended_get(item).unwrap().unwrap();
-    let item_ui = commands
-        .spawn((
-            ItemUi { item },
-            UiImage::new(icon.0.clone()),
-            Node {
-                width: Val::Px(ITEM_SIZE),
-                height: Val::Px(ITEM_SIZE),
-                ..default()
-            },
-        ))
-        .id();
+    let mut item_ui = commands.spawn((
+        ItemUi { item },
+        Node {
+            width: Val::Px(ITEM_SIZE),
+            height: Val::Px(ITEM_SIZE),
+            ..default()
+        },
+    ));
+    if let Ok(Some(icon)) = icons.extended_get(item) {
+        item_ui.insert(UiImage::new(icon.0.clone()));
+    }
+    let item_ui = item_ui.id();
     if let Ok(Some(count)) = counts.get(item) {
         if count.0 > 1 {
             commands
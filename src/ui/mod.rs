@@ -1,10 +1,14 @@
 use bevy::{
     prelude::*,
     render::texture::{ImageLoaderSettings, ImageSampler},
+    ui::UiSystem,
 };
 
+use crate::transaction::InventorySystem;
+
 pub mod carry;
 pub mod change_propagation;
+pub mod grid;
 pub mod inventory;
 pub mod item;
 pub mod layout;
@@ -16,8 +20,14 @@ pub fn plugin(app: &mut App) {
         tooltip::plugin,
         carry::plugin,
         change_propagation::plugin,
+        grid::plugin,
+        inventory::plugin,
         layout::plugin,
     ));
+    app.configure_sets(
+        PostUpdate,
+        InventorySystem::ApplyTransactions.before(UiSystem::Prepare),
+    );
 }
 
 pub const ITEM_SIZE: f32 = 16.0 * 4.0;
@@ -30,7 +40,8 @@ pub fn nearest_sampler(settings: &mut ImageLoaderSettings) {
 pub mod prelude {
     pub use super::{
         carry::Carry,
-        change_propagation::{InventoryChanged, ItemChanged, SlotChanged},
+        change_propagation::SlotChanged,
+        grid::{GridInventoryUi, SpawnGridInventoryUi},
         inventory::{InventoryUi, SpawnInventoryUi},
         item::{ItemUi, SpawnItemUi},
         nearest_sampler,
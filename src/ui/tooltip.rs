@@ -31,7 +31,9 @@ fn item_tooltip_spawn(
     if cursor_used {
         return;
     }
-    let item = names.extended_get(item.item).unwrap().unwrap();
+    let Ok(Some(item)) = names.extended_get(item.item) else {
+        return;
+    };
     let position = trigger.pointer_location.position - transform.translation().xy()
         + Vec2::splat(ITEM_SIZE / 2.0);
     commands